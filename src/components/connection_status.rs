@@ -0,0 +1,65 @@
+use dioxus::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use crate::hooks::use_bleuio::{CommandFailure, MAX_RESTARTS};
+use crate::models::session::{ConnectionState, ScanRestart};
+
+fn state_color(state: &ConnectionState) -> &'static str {
+    match state {
+        ConnectionState::Scanning => "rgb(21, 128, 61)",
+        ConnectionState::Error(_) => "rgb(185, 28, 28)",
+        _ => "rgb(202, 138, 4)",
+    }
+}
+
+// Only the last MAX_RESTARTS restarts are kept, so a full list means at least that many.
+fn restart_count(count: usize) -> String {
+    if count >= MAX_RESTARTS {
+        format!("(scan restarted {MAX_RESTARTS}+×)")
+    } else {
+        format!("(scan restarted {count}×)")
+    }
+}
+
+// One line per dongle with its live connection state. Scan restarts by the watchdog are
+// counted, with the latest ones in the tooltip, and the last command that failed is shown.
+#[component]
+pub fn ConnectionStatus(
+    states: Signal<BTreeMap<String, ConnectionState>>,
+    restarts: Signal<BTreeMap<String, VecDeque<ScanRestart>>>,
+    failures: Signal<BTreeMap<String, CommandFailure>>,
+    on_change_port: EventHandler<()>,
+) -> Element {
+    rsx! {
+        div { class: "flex flex-row items-center gap-8 px-4",
+            if states.read().is_empty() {
+                div { class: "text-sm text-gray-700", "Waiting for a BleuIO dongle…" }
+            }
+            for (name, state) in states.read().iter() {
+                div { key: "{name}", class: "flex flex-row items-center gap-1 text-sm",
+                    span { style: "display: inline-block; width: 10px; height: 10px; border-radius: 5px; background: {state_color(state)};" }
+                    span { class: "font-semibold", "{name}" }
+                    span { "{state.get_label()}" }
+                    if let Some(list) = restarts.read().get(name).filter(|l| !l.is_empty()) {
+                        span {
+                            class: "text-gray-700",
+                            title: list.iter().rev().take(10).map(|r| r.get_description()).collect::<Vec<_>>().join("\n"),
+                            {restart_count(list.len())}
+                        }
+                    }
+                    if let Some(failure) = failures.read().get(name) {
+                        span {
+                            style: "color: rgb(185, 28, 28);",
+                            title: format!("Failed at {}", failure.at.format("%Y-%m-%d %H:%M:%S")),
+                            "{failure.reason}"
+                        }
+                    }
+                }
+            }
+            button {
+                class: "border p-1 rounded-md bg-gray-500 text-sm",
+                onclick: move |_| on_change_port.call(()),
+                "Change dongle"
+            }
+        }
+    }
+}
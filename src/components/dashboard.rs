@@ -1,18 +1,67 @@
 use dioxus::prelude::*;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
-use crate::models::hibouair::*;
+use crate::models::autoexec::hibouair_preset;
+use crate::models::bleuio::{BleAddress, BleuIODeviceInfo};
+use crate::models::config::AppConfig;
+use crate::models::gatt::SensorDetails;
+use crate::models::sensor_data::{TrackedSensor, UndecodedSighting};
+use crate::models::session::{ConnectionState, ScanRestart, SessionCommand};
+use crate::models::shutdown::Shutdown;
 // use crate::models::bleuio::*; 
+use crate::components::connection_status::*;
+use crate::components::dongle_info::*;
+use crate::components::provisioning::*;
+use crate::components::sensor_details::*;
 use crate::components::sensor_panel::*;
 use crate::hooks::use_bleuio::*;
+use crate::hooks::use_shutdown::stop_sessions;
 
 #[component]
-pub fn Dashboard(port_name: String) -> Element {
-    let sensor_hash: HashMap<u32, HibouAir> = HashMap::new();
+pub fn Dashboard(config: AppConfig, on_change_port: EventHandler<()>) -> Element {
+    let sensor_hash: HashMap<u32, TrackedSensor> = HashMap::new();
     let hibs = use_signal(|| sensor_hash.clone());
+    let undecoded = use_signal(BTreeMap::<u32, UndecodedSighting>::new);
     let mut log = use_signal(|| String::new());
     
-    let _serial_task = use_bleuio(port_name, hibs);
+    let status = use_signal(BTreeMap::<String, ConnectionState>::new);
+    let info = use_signal(BTreeMap::<String, BleuIODeviceInfo>::new);
+    let restarts = use_signal(BTreeMap::<String, VecDeque<ScanRestart>>::new);
+    let details = use_signal(HashMap::<String, SensorDetails>::new);
+    let autoexec = use_signal(BTreeMap::<String, Vec<String>>::new);
+    let failures = use_signal(BTreeMap::<String, CommandFailure>::new);
+    let min_firmware = config.min_firmware;
+    let preset = hibouair_preset(&config.scan.to_command());
+
+    let dongles = DongleSignals { status, info, restarts, autoexec, failures };
+    // Stopped before another dongle is picked, so the current ones are left idle and closed.
+    let stop = use_hook(Shutdown::new);
+    let serial_task = use_bleuio(config, hibs, undecoded, details, dongles, stop.clone());
+
+    let change_port = move |_| {
+        if stop.is_requested() {
+            return;
+        }
+        let stop = stop.clone();
+        spawn(async move {
+            if !stop_sessions(&stop).await {
+                eprintln!("Dongles did not stop in time");
+            }
+            on_change_port.call(());
+        });
+    };
+
+    // Read a sensor's device information through the dongle that hears it best.
+    let read_sensor = move |sensor: &TrackedSensor| {
+        let addr = sensor.addr.parse::<BleAddress>().ok()?;
+        let dongle = sensor.get_best_dongle()?.0.clone();
+        Some(EventHandler::new(move |_| {
+            serial_task.send(DongleCommand {
+                dongle: Some(dongle.clone()),
+                command: SessionCommand::ReadSensor(addr.clone()),
+            });
+        }))
+    };
 
     rsx! {
         div {
@@ -31,6 +80,30 @@ pub fn Dashboard(port_name: String) -> Element {
                 }
             }
 
+            ConnectionStatus { states: status, restarts, failures, on_change_port: change_port }
+            DongleInfo { info, min_firmware }
+            Provisioning {
+                states: status,
+                autoexec,
+                failures,
+                preset,
+                on_command: move |cmd| serial_task.send(cmd),
+            }
+
+            if hibs.read().is_empty() {
+                div { class: "p-4 text-lg", "No sensors heard yet." }
+            }
+            // Boards that have only sent beacons this app doesn't decode.
+            for (id, sighting) in undecoded.read().iter() {
+                div { key: "{id:06X}", class: "px-4 text-sm text-gray-700",
+                    {format!(
+                        "{id:06X} heard (beacon {:#04x}, {}), waiting for a full beacon",
+                        sighting.beacon_nr,
+                        sighting.last_seen.format("%H:%M:%S"),
+                    )}
+                }
+            }
+
             div {
                 // Horizontal container for all panel groups
                 class: "flex flex-col gap-8 p-4",
@@ -39,8 +112,12 @@ pub fn Dashboard(port_name: String) -> Element {
                 for sensor in hibs.read().values() {
                     {
                         rsx! {
-                            div {
-                                SensorPanel { sensor: sensor.clone() }
+                            div { class: "rounded-xl overflow-hidden shadow-md",
+                                SensorPanel { tracked: sensor.clone() }
+                                SensorDetailsView {
+                                    details: details.read().get(&sensor.addr).cloned(),
+                                    on_read: read_sensor(sensor),
+                                }
                             }
                         }
                     }
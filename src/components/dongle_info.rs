@@ -0,0 +1,61 @@
+use dioxus::prelude::*;
+use std::collections::BTreeMap;
+use crate::models::bleuio::{BleuIODeviceInfo, FirmwareVersion, GapRole};
+
+fn role_label(role: GapRole) -> &'static str {
+    match role {
+        GapRole::Central => "Central",
+        GapRole::Peripheral => "Peripheral",
+        GapRole::Dual => "Dual",
+        GapRole::Unknown => "-",
+    }
+}
+
+// Firmware, hardware, role and MAC of every connected dongle, with a warning for
+// firmware older than `min_firmware`.
+#[component]
+pub fn DongleInfo(info: Signal<BTreeMap<String, BleuIODeviceInfo>>, min_firmware: FirmwareVersion) -> Element {
+    if info.read().is_empty() {
+        return rsx! {};
+    }
+    rsx! {
+        div { class: "px-4 pt-2",
+            table { class: "text-sm",
+                thead {
+                    tr { class: "text-left text-gray-700",
+                        th { class: "pr-6", "Dongle" }
+                        th { class: "pr-6", "Model" }
+                        th { class: "pr-6", "Firmware" }
+                        th { class: "pr-6", "Hardware" }
+                        th { class: "pr-6", "Role" }
+                        th { class: "pr-6", "MAC" }
+                        th { class: "pr-6", "Connected" }
+                    }
+                }
+                tbody {
+                    for (name, device) in info.read().iter() {
+                        tr { key: "{name}",
+                            td { class: "pr-6 font-semibold", "{name}" }
+                            td { class: "pr-6", {device.model.map(|m| m.get_name()).unwrap_or("-")} }
+                            td { class: "pr-6",
+                                "{device.firmware}"
+                                if device.is_firmware_older_than(min_firmware) {
+                                    span {
+                                        class: "ml-2 font-semibold",
+                                        style: "color: rgb(185, 28, 28);",
+                                        title: "Older firmware may not support the JSON output this app relies on. Please update the dongle.",
+                                        "⚠ older than {min_firmware}"
+                                    }
+                                }
+                            }
+                            td { class: "pr-6", "{device.hardware}" }
+                            td { class: "pr-6", "{role_label(device.role)}" }
+                            td { class: "pr-6", {device.mac.clone().unwrap_or("-".to_string())} }
+                            td { class: "pr-6", if device.connected { "Yes" } else { "No" } }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
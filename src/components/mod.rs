@@ -1,2 +1,7 @@
 pub mod sensor_panel;
-pub mod dashboard;
\ No newline at end of file
+pub mod sensor_details;
+pub mod dashboard;
+pub mod port_select;
+pub mod connection_status;
+pub mod dongle_info;
+pub mod provisioning;
@@ -0,0 +1,91 @@
+use dioxus::prelude::*;
+use crate::models::bleuio::*;
+
+#[component]
+fn PortRow(port: PortCandidate, on_select: EventHandler<String>) -> Element {
+    let vid_pid = port.get_vid_pid_string();
+    let manufacturer = port.usb.as_ref().and_then(|u| u.manufacturer.clone()).unwrap_or_default();
+    let serial_number = port.usb.as_ref().and_then(|u| u.serial_number.clone()).unwrap_or_default();
+    let row_class = if port.is_bleuio() { "bg-green-700 text-white" } else { "" };
+    let model = port.model.map(|m| m.get_name()).unwrap_or_default();
+    let port_name = port.port_name.clone();
+
+    rsx! {
+        tr { class: "{row_class}",
+            td { class: "px-2 py-1 font-semibold", "{port.port_name}" }
+            td { class: "px-2 py-1", "{vid_pid}" }
+            td { class: "px-2 py-1", "{manufacturer}" }
+            td { class: "px-2 py-1", "{serial_number}" }
+            td { class: "px-2 py-1", "{model}" }
+            td { class: "px-2 py-1",
+                button {
+                    class: "border p-1 rounded-md bg-gray-500",
+                    onclick: move |_| on_select.call(port_name.clone()),
+                    "Use"
+                }
+            }
+        }
+    }
+}
+
+// Shown when no dongle was given or found: lists the serial ports and lets the user pick one
+// or type a device path. `usb_ids` are the extra VID/PID pairs from the configuration.
+#[component]
+pub fn PortSelect(usb_ids: Vec<BleuIOVariant>, on_select: EventHandler<String>) -> Element {
+    let extra = usb_ids.clone();
+    let mut ports = use_signal(move || list_serial_ports(&extra));
+    let mut manual = use_signal(String::new);
+
+    rsx! {
+        div { class: "flex flex-col gap-4 p-4",
+            div { class: "text-2xl font-bold", "No BleuIO dongle found" }
+            div { class: "text-sm",
+                "Plug in a BleuIO dongle and press Refresh, or choose one of the serial ports below."
+            }
+
+            table { class: "text-sm",
+                thead {
+                    tr {
+                        th { class: "px-2 py-1 text-left", "Port" }
+                        th { class: "px-2 py-1 text-left", "VID:PID" }
+                        th { class: "px-2 py-1 text-left", "Manufacturer" }
+                        th { class: "px-2 py-1 text-left", "Serial number" }
+                        th { class: "px-2 py-1 text-left", "" }
+                        th { class: "px-2 py-1 text-left", "" }
+                    }
+                }
+                tbody {
+                    for port in ports.read().iter() {
+                        PortRow { key: "{port.port_name}", port: port.clone(), on_select }
+                    }
+                }
+            }
+            if ports.read().is_empty() {
+                div { class: "text-sm", "No serial ports found." }
+            }
+
+            div { class: "flex flex-row items-center gap-1",
+                button {
+                    class: "border p-1 rounded-md bg-gray-500",
+                    onclick: move |_| ports.set(list_serial_ports(&usb_ids)),
+                    "Refresh"
+                }
+            }
+
+            div { class: "flex flex-row items-center gap-1",
+                input {
+                    class: "border p-1 rounded-md text-gray-900",
+                    placeholder: "/dev/ttyACM0 or tcp://host:port",
+                    value: "{manual}",
+                    oninput: move |e| manual.set(e.value()),
+                }
+                button {
+                    class: "border p-1 rounded-md bg-gray-500",
+                    disabled: manual.read().trim().is_empty(),
+                    onclick: move |_| on_select.call(manual.read().trim().to_string()),
+                    "Connect"
+                }
+            }
+        }
+    }
+}
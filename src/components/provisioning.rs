@@ -0,0 +1,143 @@
+use dioxus::prelude::*;
+use std::collections::BTreeMap;
+use crate::hooks::use_bleuio::{CommandFailure, DongleCommand};
+use crate::models::autoexec::{diff_autoexec, AutoexecDiff};
+use crate::models::session::{ConnectionState, SessionCommand};
+
+fn diff_line(diff: &AutoexecDiff) -> (String, &'static str) {
+    match diff {
+        AutoexecDiff::Same(cmd) => (format!("  {cmd}"), "color: rgb(55, 65, 81);"),
+        AutoexecDiff::Added(cmd) => (format!("+ {cmd}"), "color: rgb(21, 128, 61);"),
+        AutoexecDiff::Removed(cmd) => (format!("- {cmd}"), "color: rgb(185, 28, 28);"),
+    }
+}
+
+// A write that changes what every dongle, or a dongle's whole list, does at power up; it is
+// only sent once confirmed.
+#[derive(Debug, Clone, PartialEq)]
+struct PendingWrite {
+    dongle: Option<String>,     // None for every dongle
+    commands: Vec<String>,
+    question: String,
+}
+
+// Read, write and clear the autoexec list of every dongle. Once a list has been read it is
+// shown as a diff against `preset`: "+" lines are what provisioning would add, "-" lines what
+// it would remove. After a write the list is read back, also when the write failed half way,
+// so the diff shows what the dongle has now, with the failure next to it.
+#[component]
+pub fn Provisioning(
+    states: Signal<BTreeMap<String, ConnectionState>>,
+    autoexec: Signal<BTreeMap<String, Vec<String>>>,
+    failures: Signal<BTreeMap<String, CommandFailure>>,
+    preset: Vec<String>,
+    on_command: EventHandler<DongleCommand>,
+) -> Element {
+    let send = move |dongle: Option<String>, command: SessionCommand| on_command.call(DongleCommand { dongle, command });
+    let mut pending = use_signal(|| None::<PendingWrite>);
+    let preset_all = preset.clone();
+
+    rsx! {
+        details { class: "px-4 pt-2 text-sm",
+            summary { class: "font-semibold text-gray-700 cursor-pointer", "Provisioning" }
+            div { class: "flex flex-col gap-3 pt-2",
+                div { class: "flex items-center gap-2",
+                    span { class: "text-gray-700", "Preset for HibouAir scanning: " }
+                    code { {preset.join(" → ")} }
+                    button {
+                        class: "border p-1 rounded-md bg-gray-500 text-white",
+                        onclick: move |_| {
+                            let count = states.read().len();
+                            pending.set(Some(PendingWrite {
+                                dongle: None,
+                                commands: preset_all.clone(),
+                                question: format!("Replace the autoexec list of all {count} dongles with the preset?"),
+                            }));
+                        },
+                        "Provision all dongles"
+                    }
+                }
+                if let Some(write) = pending() {
+                    div { class: "flex items-center gap-2",
+                        span { style: "color: rgb(185, 28, 28);", "{write.question}" }
+                        button {
+                            class: "border p-1 rounded-md bg-gray-500 text-white",
+                            onclick: move |_| {
+                                if let Some(write) = pending.take() {
+                                    send(write.dongle, SessionCommand::WriteAutoexec(write.commands));
+                                }
+                            },
+                            "Confirm"
+                        }
+                        button {
+                            class: "border p-1 rounded-md bg-gray-500 text-white",
+                            onclick: move |_| pending.set(None),
+                            "Cancel"
+                        }
+                    }
+                }
+                for name in states.read().keys().cloned() {
+                    div { key: "{name}", class: "flex flex-col gap-1",
+                        div { class: "flex items-center gap-2",
+                            span { class: "font-semibold pr-4", "{name}" }
+                            button {
+                                class: "border p-1 rounded-md bg-gray-500 text-white",
+                                onclick: {
+                                    let name = name.clone();
+                                    move |_| send(Some(name.clone()), SessionCommand::ReadAutoexec)
+                                },
+                                "Read autoexec"
+                            }
+                            button {
+                                class: "border p-1 rounded-md bg-gray-500 text-white",
+                                onclick: {
+                                    let name = name.clone();
+                                    let preset = preset.clone();
+                                    move |_| send(Some(name.clone()), SessionCommand::WriteAutoexec(preset.clone()))
+                                },
+                                "Provision for HibouAir scanning"
+                            }
+                            button {
+                                class: "border p-1 rounded-md bg-gray-500 text-white",
+                                onclick: {
+                                    let name = name.clone();
+                                    move |_| {
+                                        pending.set(Some(PendingWrite {
+                                            dongle: Some(name.clone()),
+                                            commands: Vec::new(),
+                                            question: format!("Clear the autoexec list of {name}?"),
+                                        }));
+                                    }
+                                },
+                                "Clear autoexec"
+                            }
+                        }
+                        if let Some(failure) = failures.read().get(&name) {
+                            div {
+                                style: "color: rgb(185, 28, 28);",
+                                title: format!("Failed at {}", failure.at.format("%Y-%m-%d %H:%M:%S")),
+                                "{failure.reason}"
+                            }
+                        }
+                        if let Some(current) = autoexec.read().get(&name) {
+                            {
+                                let diff = diff_autoexec(current, &preset);
+                                let unchanged = !diff.iter().any(AutoexecDiff::is_change);
+                                rsx! {
+                                    if unchanged {
+                                        div { style: "color: rgb(21, 128, 61);", "Autoexec matches the preset." }
+                                    }
+                                    pre { class: "font-mono",
+                                        for (i, (line, style)) in diff.iter().map(diff_line).enumerate() {
+                                            div { key: "{i}", style: "{style}", "{line}" }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use dioxus::prelude::*;
+use crate::models::gatt::{GattStatus, GattValue, SensorDetails};
+
+fn status_label(details: &SensorDetails) -> String {
+    let at = details.updated.format("%Y-%m-%d %H:%M:%S");
+    match &details.status {
+        GattStatus::Connecting => "Connecting…".to_string(),
+        GattStatus::Reading => "Reading characteristics…".to_string(),
+        GattStatus::Done => format!("Read {at}"),
+        GattStatus::Failed(reason) => format!("Failed {at}: {reason}"),
+    }
+}
+
+#[component]
+fn ValueTable(title: String, values: Vec<GattValue>) -> Element {
+    if values.is_empty() {
+        return rsx! {};
+    }
+    rsx! {
+        div { class: "flex flex-col gap-1",
+            div { class: "text-sm font-semibold text-gray-700", "{title}" }
+            table { class: "text-sm",
+                tbody {
+                    for value in values.iter() {
+                        tr { key: "{value.characteristic.handle}",
+                            td { class: "pr-6 text-gray-700", "{value.get_label()}" }
+                            td { class: "pr-6 font-semibold text-gray-900", "{value.get_display()}" }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Device information read from the sensor over a GATT connection, with a button to (re)read it.
+// `on_read` is None when no dongle has heard the sensor.
+#[component]
+pub fn SensorDetailsView(details: Option<SensorDetails>, on_read: Option<EventHandler<()>>) -> Element {
+    let busy = details.as_ref().is_some_and(|d| d.is_busy());
+    let device_information: Vec<GattValue> = details
+        .as_ref()
+        .map(|d| d.get_device_information().into_iter().cloned().collect())
+        .unwrap_or_default();
+    let other_values: Vec<GattValue> = details
+        .as_ref()
+        .map(|d| d.get_other_values().into_iter().cloned().collect())
+        .unwrap_or_default();
+
+    rsx! {
+        div { class: "bg-gray-50 px-6 py-3 flex flex-col gap-3 border-t border-green-800/30",
+            div { class: "flex items-center gap-4",
+                button {
+                    class: "border p-1 rounded-md bg-gray-500 text-sm text-white",
+                    disabled: busy || on_read.is_none(),
+                    title: "Pauses scanning on the dongle that hears this sensor best while connected",
+                    onclick: move |_| {
+                        if let Some(on_read) = on_read {
+                            on_read.call(());
+                        }
+                    },
+                    if details.is_some() { "Read again" } else { "Read device info" }
+                }
+                if let Some(details) = details.as_ref() {
+                    div {
+                        class: "text-sm",
+                        style: if matches!(details.status, GattStatus::Failed(_)) { "color: rgb(185, 28, 28);" } else { "" },
+                        "{status_label(details)}"
+                    }
+                }
+            }
+            if !device_information.is_empty() || !other_values.is_empty() {
+                div { class: "flex flex-row gap-12",
+                    ValueTable { title: "Device information".to_string(), values: device_information }
+                    ValueTable { title: "Other characteristics".to_string(), values: other_values }
+                }
+            }
+        }
+    }
+}
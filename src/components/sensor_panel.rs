@@ -1,37 +1,91 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
 use crate::models::hibouair::*;
-use crate::hooks::use_bleuio::LAST_TIME_STR;
+use crate::models::sensor_data::{signal_bars, TrackedSensor};
 
 fn header_title(sensor: &HibouAir) -> String {
     match sensor.get_board_type() {
         HibouAirType::Co2Sensor => "CO2 Sensor".to_string(),
         HibouAirType::PmSensor => "PM Sensor".to_string(),
+        HibouAirType::TempHumSensor => "Temp/Hum Sensor".to_string(),
+        HibouAirType::No2OutdoorWifi => "NO2 Outdoor (WiFi)".to_string(),
+        HibouAirType::No2OutdoorLtemNbiot => "NO2 Outdoor (LTE-M/NB-IoT)".to_string(),
+        HibouAirType::Co2Battery => "CO2 Battery Sensor".to_string(),
+        HibouAirType::PirSensor => "PIR Sensor".to_string(),
+        HibouAirType::Co2Noise => "CO2 Noise Sensor".to_string(),
+        HibouAirType::DuoMaster => "Duo Master".to_string(),
+        HibouAirType::DuoSlave => "Duo Slave".to_string(),
+        HibouAirType::Matrix => "Matrix Sensor".to_string(),
         _ => "Sensor".to_string(),
     }
 }
 
 #[component]
-fn Metric(label: String, value: String) -> Element {
+fn Metric(label: String, value: String, detail: Option<String>) -> Element {
     rsx! {
         div { class: "flex flex-col gap-1",
             div { class: "text-sm font-semibold text-gray-700", "{label}" }
             div { class: "text-lg font-bold text-gray-900", "{value}" }
+            if let Some(detail) = detail {
+                div { class: "text-sm text-gray-700", "{detail}" }
+            }
         }
     }
 }
 
+// VOC with its unit, and the air quality category for an IAQ index.
 #[component]
-fn SensorCard(header: String, id: String, children: Element) -> Element {
+fn VocMetric(voc: VocReading) -> Element {
+    rsx! {
+        Metric {
+            label: "VOC".to_string(),
+            value: voc.get_view(),
+            detail: voc.get_iaq_label().map(str::to_string),
+        }
+    }
+}
+
+// Signal strength as four bars plus the smoothed value, "–" when the dongle doesn't report RSSI.
+#[component]
+fn SignalBars(rssi: Option<f64>) -> Element {
+    let bars = rssi.map(signal_bars).unwrap_or(0);
+    let label = rssi.map(|r| format!("{r:.0} dBm")).unwrap_or("–".to_string());
+    rsx! {
+        div { class: "flex items-end gap-2", title: "Signal strength (smoothed)",
+            div { class: "flex items-end gap-0.5 h-5",
+                for i in 1..=4u8 {
+                    div {
+                        class: "w-1.5 rounded-sm",
+                        style: format!(
+                            "height: {}%; background-color: {};",
+                            i * 25,
+                            if i <= bars { "white" } else { "rgba(255, 255, 255, 0.3)" }
+                        ),
+                    }
+                }
+            }
+            div { class: "text-sm font-semibold", "{label}" }
+        }
+    }
+}
+
+#[component]
+fn SensorCard(header: String, id: String, tracked: TrackedSensor, children: Element) -> Element {
+    let last_seen = tracked.last_seen.format("%Y-%m-%d %H:%M:%S").to_string();
+    let heard_by = tracked.get_heard_by_string();
     rsx! {
         div {
             class: "rounded-xl overflow-hidden shadow-md border border-green-800/30",
-            title: "Last seen: {LAST_TIME_STR}",
+            title: "Last seen: {last_seen}\nHeard by: {heard_by}",
 
             // Header bar
             div { class: "bg-green-700 text-white px-6 py-4 flex items-center gap-10",
                 div { class: "text-2xl font-bold", "{header}" }
                 div { class: "text-xl font-semibold", "ID: {id}" }
+                div { class: "text-sm", "{tracked.addr}" }
+                div { class: "ml-auto",
+                    SignalBars { rssi: tracked.get_rssi() }
+                }
             }
 
             // White body
@@ -43,9 +97,11 @@ fn SensorCard(header: String, id: String, children: Element) -> Element {
 }
 
 #[component]
-fn SensorPanelCO2(sensor: HibouAir) -> Element {
+fn SensorPanelCO2(tracked: TrackedSensor) -> Element {
+    let sensor = tracked.reading;
     rsx! {
         SensorCard {
+            tracked: tracked.clone(),
             header: header_title(&sensor),
             id: sensor.get_board_id_string(),
 
@@ -54,7 +110,9 @@ fn SensorPanelCO2(sensor: HibouAir) -> Element {
                 style: "grid-template-columns: repeat(6, minmax(0, 1fr));",
 
                 Metric { label: "CO2".to_string(),      value: format!("{} ppm", sensor.get_co2()) }
-                //Metric { label: "VOC".to_string(),      value: if sensor.get_voc_view().is_empty() { "-".to_string() } else { sensor.get_voc_view() } }
+                if let Some(voc) = sensor.get_voc_reading() {
+                    VocMetric { voc }
+                }
                 Metric { label: "Humidity".to_string(), value: format!("{:.0} %rh", sensor.get_hum()) }
                 Metric { label: "Temp".to_string(),     value: format!("{:.1} °C", sensor.get_temp()) }
                 Metric { label: "Pressure".to_string(), value: format!("{:.0} hPA", sensor.get_bar()) }
@@ -65,9 +123,11 @@ fn SensorPanelCO2(sensor: HibouAir) -> Element {
 }
 
 #[component]
-fn SensorPanelPM(sensor: HibouAir) -> Element {
+fn SensorPanelPM(tracked: TrackedSensor) -> Element {
+    let sensor = tracked.reading;
     rsx! {
         SensorCard {
+            tracked: tracked.clone(),
             header: header_title(&sensor),
             id: sensor.get_board_id_string(),
 
@@ -88,7 +148,9 @@ fn SensorPanelPM(sensor: HibouAir) -> Element {
                 class: "grid gap-8 mt-6",
                 style: "grid-template-columns: repeat(6, minmax(0, 1fr));",
 
-                //Metric { label: "VOC".to_string(),   value: if sensor.get_voc_view().is_empty() { "-".to_string() } else { sensor.get_voc_view() } }
+                if let Some(voc) = sensor.get_voc_reading() {
+                    VocMetric { voc }
+                }
                 //Metric { label: "Light".to_string(), value: format!("{} Lux", sensor.get_als()) }
                 div {}
                 div {}
@@ -99,10 +161,67 @@ fn SensorPanelPM(sensor: HibouAir) -> Element {
     }
 }
 
+// The measurements of the board type, six to a row. VOC is left out when the sensor doesn't
+// supply it, and so is any value a board type whose values aren't known left empty.
+#[component]
+fn MetricGrid(tracked: TrackedSensor) -> Element {
+    let voc = tracked.get_value(Measurement::Voc).and(tracked.reading.get_voc_reading());
+    let board_type = tracked.reading.get_board_type();
+    let measurements: Vec<Measurement> = board_type
+        .get_measurements()
+        .iter()
+        .copied()
+        .filter(|m| board_type.has_known_measurements() || tracked.get_value(*m).is_some())
+        .collect();
+    rsx! {
+        div {
+            class: "grid gap-8",
+            style: "grid-template-columns: repeat(6, minmax(0, 1fr));",
+
+            for measurement in measurements.iter() {
+                if *measurement == Measurement::Voc {
+                    if let Some(voc) = voc {
+                        VocMetric { key: "{measurement.get_label()}", voc }
+                    }
+                } else {
+                    Metric {
+                        key: "{measurement.get_label()}",
+                        label: measurement.get_label().to_string(),
+                        value: tracked.get_display(*measurement).unwrap_or("–".to_string()),
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Temp/hum, NO2 outdoor, CO2 battery, PIR, CO2 noise, duo and matrix boards. Which values
+// these fill in isn't known, so the ones that aren't zero are shown and marked as such.
 #[component]
-fn SensorPanelUnknown(sensor: HibouAir) -> Element {
+fn SensorPanelMetrics(tracked: TrackedSensor) -> Element {
+    let sensor = tracked.reading;
     rsx! {
         SensorCard {
+            tracked: tracked.clone(),
+            header: header_title(&sensor),
+            id: sensor.get_board_id_string(),
+
+            MetricGrid { tracked: tracked.clone() }
+            if !sensor.get_board_type().has_known_measurements() {
+                div { class: "mt-4 text-sm text-gray-700",
+                    "Values reading zero are hidden; which ones this board type measures isn't known."
+                }
+            }
+        }
+    }
+}
+
+#[component]
+fn SensorPanelUnknown(tracked: TrackedSensor) -> Element {
+    let sensor = tracked.reading;
+    rsx! {
+        SensorCard {
+            tracked: tracked.clone(),
             header: "Unknown Sensor".to_string(),
             id: sensor.get_board_id_string(),
 
@@ -114,10 +233,11 @@ fn SensorPanelUnknown(sensor: HibouAir) -> Element {
 }
 
 #[component]
-pub fn SensorPanel(sensor: HibouAir) -> Element {
-    match sensor.get_board_type() {
-        HibouAirType::Co2Sensor => rsx! { SensorPanelCO2 { sensor: sensor.clone() } },
-        HibouAirType::PmSensor  => rsx! { SensorPanelPM  { sensor: sensor.clone() } },
-        _ => rsx! { SensorPanelUnknown { sensor: sensor.clone() } },
+pub fn SensorPanel(tracked: TrackedSensor) -> Element {
+    match tracked.reading.get_board_type() {
+        HibouAirType::Co2Sensor => rsx! { SensorPanelCO2 { tracked } },
+        HibouAirType::PmSensor  => rsx! { SensorPanelPM  { tracked } },
+        HibouAirType::Unknown => rsx! { SensorPanelUnknown { tracked } },
+        _ => rsx! { SensorPanelMetrics { tracked } },
     }
 }
@@ -1 +1,2 @@
-pub mod use_bleuio;
\ No newline at end of file
+pub mod use_bleuio;
+pub mod use_shutdown;
@@ -1,29 +1,30 @@
 use dioxus::prelude::*;
-use std::collections::HashMap;
-use serial2_tokio::SerialPort;
-use tokio::io::{BufReader, AsyncBufReadExt, AsyncWriteExt};
-use tokio::time::{timeout, Duration};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Duration;
+use chrono::{DateTime, Local, Utc};
+use futures_util::stream::FuturesUnordered;
 use futures_util::StreamExt;
-use chrono::{DateTime, Local};
 
 use crate::models::bleuio::*;
+use crate::models::bleuio_client::DongleTarget;
+use crate::models::capture::{capture_path, CaptureWriter};
+use crate::models::config::AppConfig;
+use crate::models::gatt::*;
 use crate::models::hibouair::*;
+use crate::models::sensor_data::*;
+use crate::models::sensor_reading::{ReadingsWriter, SensorReading};
+use crate::models::session::*;
+use crate::models::shutdown::Shutdown;
+
+// How often to look for dongles plugged in after the start.
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(2);
+// How many scan restarts are kept per dongle.
+pub const MAX_RESTARTS: usize = 20;
 
 pub static COUNT: GlobalSignal<i32> = Signal::global(|| 0);
 pub static LAST_TIME: GlobalSignal<DateTime<Local>> = Signal::global(|| Local::now());
 pub static LAST_TIME_STR: GlobalSignal<String> = Signal::global(|| String::new());
 
-const ATE0: &[u8; 6] = b"ATE0\r\n";
-const ATV1: &[u8; 6] = b"ATV1\r\n";
-const AT_FINDSCANDATA: &[u8;  24] = b"AT+FINDSCANDATA=FF5B07\r\n";
-
-// Utility function for adding a sensor
-fn add_sensor(mut sens: Signal<HashMap<u32, HibouAir>>, sensor: HibouAir) {
-    sens.with_mut(|s| {
-        s.insert(sensor.get_id(), sensor);
-    });
-}
-
 // Utility function for logging (commented out in original, but might be useful)
 #[cfg(feature = "logging")]
 fn logga(mut log: Signal<String>, msg: &str) {
@@ -31,179 +32,210 @@ fn logga(mut log: Signal<String>, msg: &str) {
     // log.with_mut(|l| l.push_str(msg));
 }
 
-pub fn use_bleuio(
-    port_name: String,
-    hibs: Signal<HashMap<u32, HibouAir>>,
-    // mut log: Signal<String>, // Log removed or optional? Original code passed it but commented out usage mostly.
-) -> Coroutine<BleuIOCommand> {
-    
-    use_coroutine(move |mut external_rx: UnboundedReceiver<BleuIOCommand>| {
-        let port_name_for_async = port_name.clone();
-        
-        async move {
-            // let mut app_state: AppState = AppState::OpenPort;
-
-            // logga(log_handle, &format!("Försöker öppna {}\n", port_name_for_async));
-            let port = match SerialPort::open(port_name_for_async, 115200) {
-                Ok(p) => {
-                    p.set_dtr(true).ok();
-                    p.set_rts(true).ok();
-                    p},
-                Err(_e) => {
-                    // logga(log_handle, &format!("Error: {}\n", e));
-                    return;
-                }
-            };
+// Decode a scan result (SF or ST, they carry the same fields) and merge the HibouAir beacon
+// it carries into the sensor map. Full beacons are also written to the readings file, if any.
+fn handle_scan_result(
+    mut hibs: Signal<HashMap<u32, TrackedSensor>>,
+    mut undecoded: Signal<BTreeMap<u32, UndecodedSighting>>,
+    readings: &mut Option<ReadingsWriter>,
+    dongle: &str,
+    response: BleuIOResponse,
+) {
+    let (BleuIOResponse::ScanFindData { data, rssi, addr, .. } | BleuIOResponse::ScanTarget { data, rssi, addr, .. }) = response else {
+        return;
+    };
+    let Ok(beacon) = HibouAirBeacon::from_hex(&data) else {
+        return;
+    };
+
+    if let (HibouAirBeacon::Full(reading), Some(writer)) = (&beacon, readings.as_mut()) {
+        writer.write(&SensorReading::new(reading, Utc::now(), rssi)).ok();
+    }
+    let merged = hibs.with_mut(|s| undecoded.with_mut(|u| merge_beacon(s, u, dongle, beacon, &addr, rssi, Local::now())));
+    if merged {
+        *LAST_TIME.write() = Local::now();
+        *LAST_TIME_STR.write() = LAST_TIME.read().format("%Y-%m-%d %H:%M:%S").to_string();
+        COUNT.with_mut(|c| *c += 1);
+    }
+}
 
-            // Dela upp porten i läsare och skrivare för att kunna använda båda i select!
-            let (reader, mut writer) = tokio::io::split(port);
-            let mut buffered_reader = BufReader::new(reader);
-            let mut read_buffer = String::new();
+// A command from the UI, for one dongle or for all of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DongleCommand {
+    pub dongle: Option<String>,     // session name as in the status line, None for every dongle
+    pub command: SessionCommand,
+}
 
-            // Current coomunicating state with the BleuIO dongle.
-            let mut last_cmd: &[u8];
+// What the UI shows about each dongle, keyed by session name.
+#[derive(Clone, Copy, PartialEq)]
+pub struct DongleSignals {
+    pub status: Signal<BTreeMap<String, ConnectionState>>,
+    pub info: Signal<BTreeMap<String, BleuIODeviceInfo>>,
+    pub restarts: Signal<BTreeMap<String, VecDeque<ScanRestart>>>,   // the last MAX_RESTARTS
+    pub autoexec: Signal<BTreeMap<String, Vec<String>>>,
+    pub failures: Signal<BTreeMap<String, CommandFailure>>,
+}
 
-            // logga(log_handle, "Port öppen. Väntar...\n");
+// The last command a dongle failed to carry out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandFailure {
+    pub reason: String,
+    pub at: DateTime<Local>,
+}
 
-            // Skapa en intern kanal
-            let (internal_tx, mut internal_rx) = futures_channel::mpsc::unbounded::<BleuIOCommand>();
-            // let initial_tx = internal_tx.clone();
+// Run one dongle's session until its command channel closes.
+async fn run_session(
+    mut session: BleuIOSession,
+    mut commands: UnboundedReceiver<SessionCommand>,
+    hibs: Signal<HashMap<u32, TrackedSensor>>,
+    undecoded: Signal<BTreeMap<u32, UndecodedSighting>>,
+    mut readings: Option<ReadingsWriter>,
+    mut details: Signal<HashMap<String, SensorDetails>>,
+    dongles: DongleSignals,
+) {
+    let DongleSignals { mut status, mut info, mut restarts, mut autoexec, mut failures } = dongles;
+    let dongle = session.get_name();
+    status.with_mut(|s| {
+        s.insert(dongle.clone(), ConnectionState::Waiting);
+    });
 
-            // 1. Skicka initialt kommando direkt
-            // initial_tx.unbounded_send(BleuIOCommand::At).ok();
-            // writer.write_all(b"ATE0\r\n").await.ok();
-            writer.write_all(ATE0).await.ok();
-            last_cmd = ATE0;
-            let mut last_error: i64 = 0;
+    // Reconnects by itself when the dongle is unplugged.
+    session.run(&mut commands, |event| {
+        if let Some(state) = ConnectionState::from_event(&event) {
+            status.with_mut(|s| {
+                s.insert(dongle.clone(), state);
+            });
+        }
+        match event {
+            SessionEvent::ScanResult(response) => handle_scan_result(hibs, undecoded, &mut readings, &dongle, response),
+            SessionEvent::DeviceInfo(device_info) => {
+                info.with_mut(|i| {
+                    i.insert(dongle.clone(), device_info);
+                });
+            }
+            SessionEvent::ScanRestarted(restart) => {
+                restarts.with_mut(|r| {
+                    let list = r.entry(dongle.clone()).or_default();
+                    if list.len() == MAX_RESTARTS {
+                        list.pop_front();
+                    }
+                    list.push_back(restart);
+                });
+            }
+            SessionEvent::CommandFailed(reason) => {
+                eprintln!("{dongle}: {reason}");
+                failures.with_mut(|f| {
+                    f.insert(dongle.clone(), CommandFailure { reason, at: Local::now() });
+                });
+            }
+            SessionEvent::Autoexec(list) => {
+                autoexec.with_mut(|a| {
+                    a.insert(dongle.clone(), list);
+                });
+            }
+            SessionEvent::Gatt(gatt) => {
+                details.with_mut(|d| apply_gatt_event(d, gatt, Local::now()));
+            }
+            SessionEvent::Disconnected(_reason) => {
+                // The next dongle on this port may run other firmware; ATI is asked again on reconnect.
+                info.with_mut(|i| {
+                    i.remove(&dongle);
+                });
+                // logga(log_handle, &format!("Frånkopplad: {}\n", reason));
+            }
+            _ => {}
+        }
+    }).await;
+}
+
+// The sessions run until the app shuts down or `stop` is requested, e.g. to pick another
+// dongle; either way each one stops its scan and closes its port first.
+pub fn use_bleuio(
+    config: AppConfig,
+    hibs: Signal<HashMap<u32, TrackedSensor>>,
+    undecoded: Signal<BTreeMap<u32, UndecodedSighting>>,
+    details: Signal<HashMap<String, SensorDetails>>,
+    dongles: DongleSignals,
+    stop: Shutdown,
+) -> Coroutine<DongleCommand> {
+
+    let shutdown = use_context::<Shutdown>();
+
+    use_coroutine(move |mut external_rx: UnboundedReceiver<DongleCommand>| {
+        let config = config.clone();
+        let shutdown = shutdown.clone();
+        let stop = stop.clone();
+
+        async move {
+            // Keeps the app from exiting until every dongle has been stopped and released.
+            let _guard = shutdown.guard();
+            let _stop_guard = stop.guard();
+
+            let readings = config.readings.as_ref().and_then(|path| {
+                ReadingsWriter::create(path)
+                    .map_err(|e| eprintln!("Could not create readings file {}: {e}", path.display()))
+                    .ok()
+            });
+
+            // One scanning session per dongle, all feeding the same sensor map.
+            let start_session = |target: DongleTarget| {
+                let (tx, rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+                let mut session = BleuIOSession::new(target);
+                session.set_scan_command(config.scan.to_command());
+                session.set_silence_window(config.scan.silence_window);
+                session.set_usb_variants(config.usb_ids.clone());
+                if let Some(path) = &config.capture {
+                    let path = capture_path(path, &session.get_name());
+                    match CaptureWriter::create(&path) {
+                        Ok(writer) => session.set_capture(writer),
+                        Err(e) => eprintln!("Could not create capture file {}: {e}", path.display()),
+                    }
+                }
+                ((session.get_name(), tx), run_session(session, rx, hibs, undecoded, readings.clone(), details, dongles))
+            };
 
+            let mut targets = config.targets();
+            let mut senders = Vec::new();
+            let mut sessions = FuturesUnordered::new();
+            for target in targets.iter().cloned() {
+                let (sender, session) = start_session(target);
+                senders.push(sender);
+                sessions.push(session);
+            }
+
+            // Commands from the UI go to the dongle they name, or to every dongle. Dongles
+            // plugged in later are picked up by polling, each once: a session keeps waiting for
+            // its dongle after it is unplugged.
+            let mut hotplug = tokio::time::interval(HOTPLUG_INTERVAL);
             loop {
                 tokio::select! {
-                    // GREN 1: Läs inkommande data från USB (fram till LF)
-                    res = timeout(Duration::from_secs(5), buffered_reader.read_line(&mut read_buffer)) => {
-                        match res {
-                            Ok(Ok(0)) => break, // Porten stängdes
-                            Ok(Ok(_)) => {
-                                let clean_line = read_buffer.trim_end_matches(['\r', '\n']).to_string();
-                                read_buffer.clear();
-                                // logga(log_handle, &format!("{}\n", clean_line));
-                                match parse_bleuio_result(&clean_line) {
-                                    Ok(v) => {
-                                        let t = get_bleuio_result_type(&v);
-                                        match &t {
-                                            BleuIOResponseType::AcknowledgementResponse => {
-                                                // Received line with possible error code - let's hope it is success!
-                                                // In any case, save it for later.
-                                                last_error = v["err"].as_i64().unwrap_or(-1); 
-                                                // let ec = BleuIOErrorCode::try_from(last_error);
-                                                // logga(log_handle, &format!("Error code: {}, msg: {}, ec: {:?}\n", last_error, &v["errMsg"], &ec));
-                                            },
-                                            BleuIOResponseType::EndResponse => {
-                                                // Last line of response received.
-                                                if last_error == 0 {
-                                                    // logga(log_handle, "Operation slutförd utan fel.\n");
-                                                    if last_cmd == ATE0 {
-                                                        // Echo off successful
-                                                        // logga(log_handle, "Echo avstängt\n");
-                                                        writer.write_all(ATV1).await.ok();
-                                                        last_cmd = ATV1;
-                                                    } else if last_cmd == ATV1 {
-                                                        // logga(log_handle, "Verbose läge aktiverat\n");
-                                                        writer.write_all(AT_FINDSCANDATA).await.ok();
-                                                        last_cmd = AT_FINDSCANDATA;
-                                                    }
-                                                } else {
-                                                    // logga(log_handle, &format!("Operation slutförd med felkod {}\n", last_error));
-                                                }
-                                            },
-                                            BleuIOResponseType::ScanFindDataResponse => {
-                                                // Scan completed.
-                                                // logga(log_handle, &format!("address: {} data: {}\n", &v["addr"], &v["data"]));
-                                                let data = &v["data"].as_str().unwrap_or("");
-                                                if data.len() > 60 {
-                                                    match HibouAir::from_hex(data) {
-                                                        Ok(hibou) => {
-                                                            // ✅ Only accept the "full" HibouAir payload (Beacon 0x05)
-                                                            if hibou.get_beacon_nr() != 0x05 {
-                                                                continue;
-                                                            }
-
-                                                            add_sensor(hibs, hibou);
-                                                            *LAST_TIME.write() = Local::now();
-                                                            *LAST_TIME_STR
-                                                                .write() = LAST_TIME.read().format("%Y-%m-%d %H:%M:%S").to_string();
-                                                            COUNT.with_mut(|c| *c += 1);
-                                                        }
-                                                        Err(_e) => {
-                                                            // ignore
-                                                        }
-                                                    }
-
-                                                }
-                                            },
-                                            _ => {}
-                                        }
-                                    }
-                                    Err(_e) => {
-                                        // We may end up here for a couple of reasons:
-                                        // 1. The line is not JSON (e.g. "OK" or "ERROR")
-                                        // 2. The line is malformed JSON
-                                        // logga(log_handle, &format!("JSON error: {}\n", e));
-                                        // logga(log_handle, &format!("Rådata: {}\n", clean_line));
-                                        if last_cmd == ATE0 {
-                                            if clean_line == "ECHO OFF" {
-                                                // Echo off successful
-                                                // logga(log_handle, "Echo avstängt\n");
-                                                writer.write_all(ATV1).await.ok();
-                                                last_cmd = ATV1;
-                                            // } else {
-                                            //     logga(log_handle, "Fel vid avstängning av echo\n");
-                                            }
-                                        } else if last_cmd == ATV1 {
-                                            if clean_line == "VERBOSE ON" {
-                                                // logga(log_handle, "Verbose läge aktiverat\n");
-                                                writer.write_all(AT_FINDSCANDATA).await.ok();
-                                                last_cmd = AT_FINDSCANDATA;
-                                            // } else {
-                                            //     logga(log_handle, "Fel vid aktivering av verbose läge\n");
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            Ok(Err(_e)) => {
-                                // logga(log_handle, &format!("Läsfel: {}\n", e));
-                                break;
+                    cmd = external_rx.next() => {
+                        let Some(cmd) = cmd else { break };
+                        for (name, tx) in &senders {
+                            if cmd.dongle.as_ref().is_none_or(|dongle| dongle == name) {
+                                tx.unbounded_send(cmd.command.clone()).ok();
                             }
-                            Err(_) => {
-                                // Detta händer om 5 sekunder går utan att read_line blir klar
-                                // logga(log_handle, "Timeout.\n");
-                            }
-                        }
-                    }
-
-                    // GREN 2: Lyssna på kommandon från Dioxus UI (rx)
-                    ext_msg = external_rx.next() => {
-                        if let Some(cmd) = ext_msg {
-                            internal_tx.unbounded_send(cmd).ok();
-                        } else {
-                            break; // Avsluta om UI-kanalen dör
                         }
                     }
-
-                    // GREN 3: Här körs ALL logik (både från UI och interna triggers)
-                    cmd_to_exec = internal_rx.next() => {
-                        if let Some(cmd) = cmd_to_exec {
-                            // logga(log_handle, &format!("Kör kommando: {:?}", cmd));
-                            match cmd {
-                                BleuIOCommand::At => {writer.write_all(b"AT\r\n").await.ok();},
-                                BleuIOCommand::AtI=> {writer.write_all(b"ATI\r\n").await.ok();},
-                                BleuIOCommand::AtCentral => {writer.write_all(b"AT+CENTRAL\r\n").await.ok();},
-                                BleuIOCommand::AtFindscandata => {writer.write_all(b"AT+FINDSCANDATA=FF5B07\r\n").await.ok();},
+                    _ = hotplug.tick(), if config.is_auto_detect() => {
+                        for target in config.targets() {
+                            if !targets.contains(&target) {
+                                targets.push(target.clone());
+                                let (sender, session) = start_session(target);
+                                senders.push(sender);
+                                sessions.push(session);
                             }
                         }
                     }
+                    _ = sessions.next(), if !sessions.is_empty() => {}
+                    _ = shutdown.requested() => break,
+                    _ = stop.requested() => break,
                 }
             }
+
+            // Dropping the senders makes every session stop its scan and close its port.
+            drop(senders);
+            while sessions.next().await.is_some() {}
         }
     })
-}
\ No newline at end of file
+}
@@ -0,0 +1,62 @@
+use dioxus::desktop::tao::event::Event;
+use dioxus::desktop::{use_wry_event_handler, WindowEvent};
+use dioxus::prelude::*;
+use tokio::time::Duration;
+
+use crate::models::shutdown::Shutdown;
+
+// Longest the sessions get to stop their scans and close their ports before we exit anyway.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Ask every session holding a guard of `shutdown` to stop and wait until they have released
+// their dongles. Returns false if they didn't within SHUTDOWN_TIMEOUT.
+pub async fn stop_sessions(shutdown: &Shutdown) -> bool {
+    shutdown.request();
+    shutdown.wait_idle(SHUTDOWN_TIMEOUT).await
+}
+
+// Stop every session, then exit.
+async fn quit(shutdown: Shutdown) {
+    if shutdown.is_requested() {
+        return;
+    }
+    if !stop_sessions(&shutdown).await {
+        eprintln!("Dongles did not stop in time, exiting anyway");
+    }
+    std::process::exit(0);
+}
+
+// Complete on Ctrl-C, or SIGTERM on Unix.
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+            }
+            return;
+        }
+    }
+    tokio::signal::ctrl_c().await.ok();
+}
+
+// Shut down in order when the window is closed or the process gets SIGINT/SIGTERM.
+// The window is configured to only hide on close (see main), so the app stays alive
+// until the sessions are done.
+pub fn use_shutdown(shutdown: Shutdown) {
+    let on_close = shutdown.clone();
+    use_wry_event_handler(move |event, _| {
+        if let Event::WindowEvent { event: WindowEvent::CloseRequested, .. } = event {
+            spawn(quit(on_close.clone()));
+        }
+    });
+
+    use_hook(move || {
+        spawn(async move {
+            wait_for_signal().await;
+            quit(shutdown).await;
+        });
+    });
+}
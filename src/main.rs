@@ -1,6 +1,6 @@
 #![allow(non_snake_case)]
 use dioxus::prelude::*;
-use dioxus::desktop::{Config, WindowBuilder, LogicalSize};
+use dioxus::desktop::{Config, WindowBuilder, WindowCloseBehaviour, LogicalSize};
 
 // Modules
 pub mod components;
@@ -8,7 +8,10 @@ pub mod models;
 pub mod hooks;
 
 use crate::components::dashboard::*;
-use crate::models::bleuio::find_bleuio;
+use crate::components::port_select::*;
+use crate::hooks::use_shutdown::*;
+use crate::models::config::AppConfig;
+use crate::models::shutdown::Shutdown;
 
 const FAVICON: Asset = asset!("/assets/favicon.ico");
 const MAIN_CSS: Asset = asset!("/assets/main.css");
@@ -19,20 +22,35 @@ fn main() {
     #[cfg(target_os = "macos")]
     macos_app_nap::prevent(); 
 
+    let config = match AppConfig::from_args(std::env::args().skip(1)) {
+        Ok(c) => c,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
+
     // 1. Define your window configuration
     let window = WindowBuilder::new()
         .with_title("Sensor Dashboard")
         .with_inner_size(LogicalSize::new(1100.0, 600.0)); // Width, Height
 
-    // 2. Launch with the custom config
+    // 2. Launch with the custom config. Closing the window only hides it so the dongles can
+    // be stopped before the app exits, see use_shutdown.
     LaunchBuilder::new()
-        .with_cfg(Config::new().with_window(window))
+        .with_cfg(Config::new().with_window(window).with_close_behaviour(WindowCloseBehaviour::WindowHides))
+        .with_context(config)
+        .with_context(Shutdown::new())
         .launch(App);
 }
 
 #[component]
 fn App() -> Element {
-    let port_name = find_bleuio();
+    let initial_config = use_context::<AppConfig>();
+    use_shutdown(use_context::<Shutdown>());
+    let mut selecting_port = use_signal(|| initial_config.needs_port_selection());
+    let mut config = use_signal(|| initial_config.clone());
+
     rsx! {
         // Länka till den kompilerade filen (dx lägger den i assets/main.css som standard)
         document::Stylesheet { href: CSS }
@@ -40,6 +58,19 @@ fn App() -> Element {
         document::Link { rel: "icon", href: FAVICON }
         document::Link { rel: "stylesheet", href: MAIN_CSS }
         document::Link { rel: "stylesheet", href: TAILWIND_CSS }
-        Dashboard { port_name }
+        if selecting_port() {
+            PortSelect {
+                usb_ids: config.read().usb_ids.clone(),
+                on_select: move |port: String| {
+                    config.with_mut(|c| c.ports = vec![port]);
+                    selecting_port.set(false);
+                }
+            }
+        } else {
+            Dashboard {
+                config: config(),
+                on_change_port: move |_| selecting_port.set(true),
+            }
+        }
     }
 }
@@ -0,0 +1,150 @@
+use serde_json::Value;
+
+use crate::models::bleuio::{BleuIOCommand, BleuIOResponse};
+
+/* AT+AUTOEXEC without argument lists the commands run at power up, one R line each:
+    {"R":7,"autoexec":"ATV1"}
+    {"R":7,"autoexec":"AT+FINDSCANDATA=FF5B07"}
+   Without verbose mode the commands come as plain text lines.
+*/
+pub fn parse_autoexec_list(lines: &[BleuIOResponse]) -> Vec<String> {
+    let mut list = Vec::new();
+    for line in lines {
+        match line {
+            BleuIOResponse::Reply { data, .. } => match data.get("autoexec") {
+                Some(Value::String(cmd)) => list.push(cmd.trim().to_string()),
+                Some(Value::Array(cmds)) => list.extend(cmds.iter().filter_map(Value::as_str).map(|c| c.trim().to_string())),
+                _ => {}
+            },
+            BleuIOResponse::Text(text) if text.starts_with("AT") => list.push(text.trim().to_string()),
+            _ => {}
+        }
+    }
+    list.retain(|cmd| !cmd.is_empty());
+    list
+}
+
+// Autoexec list that makes a dongle scan for HibouAir sensors by itself as soon as it is
+// powered, with the JSON output and RSSI this app expects.
+pub fn hibouair_preset(scan: &BleuIOCommand) -> Vec<String> {
+    [BleuIOCommand::Ate0, BleuIOCommand::Atv1, BleuIOCommand::AtShowRssi(true), scan.clone()]
+        .iter()
+        .map(BleuIOCommand::to_command_string)
+        .collect()
+}
+
+// One line of the difference between the current and the wanted autoexec list.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AutoexecDiff {
+    Same(String),
+    Added(String),      // only in the wanted list
+    Removed(String),    // only in the current list
+}
+
+impl AutoexecDiff {
+    pub fn is_change(&self) -> bool {
+        !matches!(self, AutoexecDiff::Same(_))
+    }
+}
+
+// Line by line difference, keeping the order of both lists (longest common subsequence).
+// Commands are compared without regard to case, as the dongle accepts either.
+pub fn diff_autoexec(current: &[String], wanted: &[String]) -> Vec<AutoexecDiff> {
+    let same = |a: &String, b: &String| a.eq_ignore_ascii_case(b);
+    let (n, m) = (current.len(), wanted.len());
+    // lcs[i][j] = length of the common subsequence of current[i..] and wanted[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if same(&current[i], &wanted[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if same(&current[i], &wanted[j]) {
+            diff.push(AutoexecDiff::Same(wanted[j].clone()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(AutoexecDiff::Removed(current[i].clone()));
+            i += 1;
+        } else {
+            diff.push(AutoexecDiff::Added(wanted[j].clone()));
+            j += 1;
+        }
+    }
+    diff.extend(current[i..].iter().cloned().map(AutoexecDiff::Removed));
+    diff.extend(wanted[j..].iter().cloned().map(AutoexecDiff::Added));
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::parse_bleuio_line;
+
+    fn list(cmds: &[&str]) -> Vec<String> {
+        cmds.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_autoexec_list() {
+        let verbose: Vec<BleuIOResponse> = [
+            r#"{"C":7,"cmd":"AT+AUTOEXEC"}"#,
+            r#"{"A":7,"err":0,"errMsg":"ok"}"#,
+            r#"{"R":7,"autoexec":"ATV1"}"#,
+            r#"{"R":7,"autoexec":"AT+FINDSCANDATA=FF5B07"}"#,
+            r#"{"E":7,"nol":5}"#,
+        ]
+        .iter()
+        .map(|l| parse_bleuio_line(l))
+        .collect();
+        assert_eq!(parse_autoexec_list(&verbose), list(&["ATV1", "AT+FINDSCANDATA=FF5B07"]));
+
+        let array = [parse_bleuio_line(r#"{"R":7,"autoexec":["ATE0","AT+SHOWRSSI=1"]}"#)];
+        assert_eq!(parse_autoexec_list(&array), list(&["ATE0", "AT+SHOWRSSI=1"]));
+
+        let text = [parse_bleuio_line("ATE0"), parse_bleuio_line("AT+CENTRAL")];
+        assert_eq!(parse_autoexec_list(&text), list(&["ATE0", "AT+CENTRAL"]));
+        assert!(parse_autoexec_list(&[parse_bleuio_line(r#"{"E":7,"nol":3}"#)]).is_empty());
+    }
+
+    #[test]
+    fn test_hibouair_preset() {
+        assert_eq!(
+            hibouair_preset(&BleuIOCommand::hibouair_scan()),
+            list(&["ATE0", "ATV1", "AT+SHOWRSSI=1", "AT+FINDSCANDATA=FF5B07"])
+        );
+    }
+
+    #[test]
+    fn test_diff_autoexec() {
+        let wanted = list(&["ATE0", "ATV1", "AT+SHOWRSSI=1", "AT+FINDSCANDATA=FF5B07"]);
+
+        let diff = diff_autoexec(&[], &wanted);
+        assert!(diff.iter().all(|d| matches!(d, AutoexecDiff::Added(_))));
+        assert_eq!(diff.len(), 4);
+
+        let diff = diff_autoexec(&list(&["ate0", "ATV1", "AT+SHOWRSSI=1", "AT+FINDSCANDATA=FF5B07"]), &wanted);
+        assert!(!diff.iter().any(AutoexecDiff::is_change));
+
+        let diff = diff_autoexec(&list(&["ATE0", "AT+CENTRAL", "AT+SHOWRSSI=1", "AT+GAPSCAN"]), &wanted);
+        assert_eq!(diff, vec![
+            AutoexecDiff::Same("ATE0".to_string()),
+            AutoexecDiff::Removed("AT+CENTRAL".to_string()),
+            AutoexecDiff::Added("ATV1".to_string()),
+            AutoexecDiff::Same("AT+SHOWRSSI=1".to_string()),
+            AutoexecDiff::Removed("AT+GAPSCAN".to_string()),
+            AutoexecDiff::Added("AT+FINDSCANDATA=FF5B07".to_string()),
+        ]);
+
+        let diff = diff_autoexec(&wanted, &[]);
+        assert_eq!(diff.iter().filter(|d| matches!(d, AutoexecDiff::Removed(_))).count(), 4);
+    }
+}
@@ -1,16 +1,275 @@
 use serialport5::*;
-use serde_json::{Result, Value};
+use serde::{Deserialize, Deserializer};
+use serde::de::Error as _;
+use serde_json::{Map, Result, Value};
 use std::convert::TryFrom;
 
 const BLUEIO_VID: u16 = 0x2dcf;
 const BLUEIO_PID: u16 = 0x6002;
 
+// Dongle generations we know about. `Other` is a VID/PID pair from the configuration that
+// isn't tied to a known model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BleuIOModel {
+    BleuIO,
+    BleuIOPro,
+    Other,
+}
+
+// What a model supports beyond scanning. The client checks these before sending commands
+// that older or unknown dongles would reject.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+    pub show_rssi: bool,        // AT+SHOWRSSI
+    pub autoexec: bool,         // AT+AUTOEXEC and AT+CLRAUTOEXEC
+    pub gatt_client: bool,      // AT+GAPCONNECT, AT+GETSERVICES and AT+GATTCREAD
+}
+
+impl BleuIOModel {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            BleuIOModel::BleuIO => "BleuIO",
+            BleuIOModel::BleuIOPro => "BleuIO Pro",
+            BleuIOModel::Other => "BleuIO (other)",
+        }
+    }
+
+    // Unknown models still get RSSI in their scan results, which the dashboard relies on, but
+    // nothing that changes the dongle (autoexec) or connects to sensors.
+    pub fn get_capabilities(&self) -> Capabilities {
+        match self {
+            BleuIOModel::BleuIO | BleuIOModel::BleuIOPro => Capabilities { show_rssi: true, autoexec: true, gatt_client: true },
+            BleuIOModel::Other => Capabilities { show_rssi: true, autoexec: false, gatt_client: false },
+        }
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        BleuIOModel::BleuIO.get_capabilities()
+    }
+}
+
+impl std::str::FromStr for BleuIOModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bleuio" => Ok(BleuIOModel::BleuIO),
+            "pro" | "bleuio-pro" => Ok(BleuIOModel::BleuIOPro),
+            "other" => Ok(BleuIOModel::Other),
+            _ => Err(format!("Unknown BleuIO model: {s}")),
+        }
+    }
+}
+
+// A USB VID/PID pair and the model it identifies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BleuIOVariant {
+    pub vid: u16,
+    pub pid: u16,
+    pub model: BleuIOModel,
+}
+
+// Dongles recognised out of the box. The BleuIO Pro is left out until its USB id is
+// published by the vendor; until then it is recognised with `--usb-id <vid:pid>=pro`.
+pub const KNOWN_VARIANTS: [BleuIOVariant; 1] = [
+    BleuIOVariant { vid: BLUEIO_VID, pid: BLUEIO_PID, model: BleuIOModel::BleuIO },
+];
+
+// Parse "2dcf:6002" or "2dcf:6002=pro". Without a model the pair is `Other`.
+impl std::str::FromStr for BleuIOVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (ids, model) = match s.split_once('=') {
+            Some((ids, model)) => (ids, model.parse()?),
+            None => (s, BleuIOModel::Other),
+        };
+        let parse_id = |id: &str| u16::from_str_radix(id.trim().trim_start_matches("0x"), 16).ok();
+        match ids.split_once(':').map(|(vid, pid)| (parse_id(vid), parse_id(pid))) {
+            Some((Some(vid), Some(pid))) => Ok(BleuIOVariant { vid, pid, model }),
+            _ => Err(format!("Invalid USB id, expected VID:PID in hex: {s}")),
+        }
+    }
+}
+
+// Address type as written in front of a MAC address, e.g. "[1]D2:B1:28:3F:42:D4".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BleAddressType {
+    Public = 0,
+    Random = 1,
+}
+
+// A BLE device address with its address type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BleAddress {
+    pub addr_type: BleAddressType,
+    pub mac: String,                // "D2:B1:28:3F:42:D4", upper case
+}
+
+impl BleAddress {
+    pub fn new(addr_type: BleAddressType, mac: &str) -> std::result::Result<Self, String> {
+        let parts: Vec<&str> = mac.split(':').collect();
+        if parts.len() != 6 || !parts.iter().all(|p| p.len() == 2 && u8::from_str_radix(p, 16).is_ok()) {
+            return Err(format!("Invalid MAC address: {mac}"));
+        }
+        Ok(BleAddress { addr_type, mac: mac.to_uppercase() })
+    }
+}
+
+impl std::str::FromStr for BleAddress {
+    type Err = String;
+
+    // Accepts "[0]MAC", "[1]MAC" or a bare MAC, which is taken as a random address
+    // (what HibouAir sensors use).
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(mac) = s.strip_prefix("[0]") {
+            BleAddress::new(BleAddressType::Public, mac)
+        } else if let Some(mac) = s.strip_prefix("[1]") {
+            BleAddress::new(BleAddressType::Random, mac)
+        } else {
+            BleAddress::new(BleAddressType::Random, s)
+        }
+    }
+}
+
+impl std::fmt::Display for BleAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}]{}", self.addr_type as u8, self.mac)
+    }
+}
+
+// Scan parameters for AT+SCANPARAM. Interval and window are in units of 0.625 ms.
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanParams {
+    pub active: bool,               // active scanning requests scan responses
+    pub interval: u16,
+    pub window: u16,
+    pub filter_duplicates: bool,
+}
+
+// What AT+SCANFILTER should match on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanFilter {
+    Uuid(String),       // service UUID in hex
+    Name(String),       // (part of) the device name
+    Data(String),       // manufacturer specific data in hex
+    Clear,              // remove all filters
+    Show,               // list the current filters
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum BleuIOCommand {
     At,
     AtI,
+    Ate0,
+    Ate1,
+    Atv0,
+    Atv1,
+    Atr,                                                        // reset the dongle
     AtCentral,
-    AtFindscandata,
+    AtPeripheral,
+    AtDual,
+    AtShowRssi(bool),
+    AtGetMac,
+    AtGapScan(Option<u32>),                                     // optional duration in seconds
+    AtFindscandata { pattern: String, duration: Option<u32> },  // hex pattern to look for in advertising data
+    AtScanTarget { targets: Vec<BleAddress>, duration: Option<u32> },
+    AtScanParam(ScanParams),
+    AtScanFilter(ScanFilter),
+    StopScan,                                                   // Ctrl-C, stops any ongoing scan
+    AtCancelConnect,
+    AtGapConnect(BleAddress),
+    AtGapDisconnect,
+    AtGapDisconnectAll,
+    AtGetServices,                                              // list services and characteristics of the connected device
+    AtGattcRead(u16),                                           // characteristic handle
+    AtAutoExecList,
+    AtAutoExecAdd(String),                                      // command to run at power up
+    AtClrAutoExec,
+}
+
+// Return "=<seconds>" for commands taking an optional duration.
+fn duration_arg(duration: &Option<u32>) -> String {
+    duration.map(|d| format!("={d}")).unwrap_or_default()
+}
+
+impl BleuIOCommand {
+    // The scan this app normally runs: everything advertising HibouAir manufacturer data.
+    pub fn hibouair_scan() -> Self {
+        BleuIOCommand::AtFindscandata { pattern: "FF5B07".to_string(), duration: None }
+    }
+
+    // Return true for the commands that start a scan.
+    pub fn starts_scan(&self) -> bool {
+        matches!(
+            self,
+            BleuIOCommand::AtGapScan(_) | BleuIOCommand::AtFindscandata { .. } | BleuIOCommand::AtScanTarget { .. }
+        )
+    }
+
+    // Return the command text without line terminator.
+    pub fn to_command_string(&self) -> String {
+        match self {
+            BleuIOCommand::At => "AT".to_string(),
+            BleuIOCommand::AtI => "ATI".to_string(),
+            BleuIOCommand::Ate0 => "ATE0".to_string(),
+            BleuIOCommand::Ate1 => "ATE1".to_string(),
+            BleuIOCommand::Atv0 => "ATV0".to_string(),
+            BleuIOCommand::Atv1 => "ATV1".to_string(),
+            BleuIOCommand::Atr => "ATR".to_string(),
+            BleuIOCommand::AtCentral => "AT+CENTRAL".to_string(),
+            BleuIOCommand::AtPeripheral => "AT+PERIPHERAL".to_string(),
+            BleuIOCommand::AtDual => "AT+DUAL".to_string(),
+            BleuIOCommand::AtShowRssi(on) => format!("AT+SHOWRSSI={}", *on as u8),
+            BleuIOCommand::AtGetMac => "AT+GETMAC".to_string(),
+            BleuIOCommand::AtGapScan(duration) => format!("AT+GAPSCAN{}", duration_arg(duration)),
+            BleuIOCommand::AtFindscandata { pattern, duration } => {
+                format!("AT+FINDSCANDATA={}{}", pattern.to_uppercase(), duration_arg(duration))
+            }
+            BleuIOCommand::AtScanTarget { targets, duration } => {
+                let targets: Vec<String> = targets.iter().map(|t| t.to_string()).collect();
+                format!("AT+SCANTARGET={}{}", targets.join(";"), duration_arg(duration))
+            }
+            BleuIOCommand::AtScanParam(p) => format!(
+                "AT+SCANPARAM={}={}={}={}",
+                p.active as u8, p.interval, p.window, p.filter_duplicates as u8
+            ),
+            BleuIOCommand::AtScanFilter(filter) => match filter {
+                ScanFilter::Uuid(uuid) => format!("AT+SCANFILTER=UUID={}", uuid.to_uppercase()),
+                ScanFilter::Name(name) => format!("AT+SCANFILTER=NAME={name}"),
+                ScanFilter::Data(data) => format!("AT+SCANFILTER=DATA={}", data.to_uppercase()),
+                ScanFilter::Clear => "AT+SCANFILTER=CLEAR".to_string(),
+                ScanFilter::Show => "AT+SCANFILTER=?".to_string(),
+            },
+            BleuIOCommand::StopScan => "\x03".to_string(),
+            BleuIOCommand::AtCancelConnect => "AT+CANCELCONNECT".to_string(),
+            BleuIOCommand::AtGapConnect(addr) => format!("AT+GAPCONNECT={addr}"),
+            BleuIOCommand::AtGapDisconnect => "AT+GAPDISCONNECT".to_string(),
+            BleuIOCommand::AtGapDisconnectAll => "AT+GAPDISCONNECTALL".to_string(),
+            BleuIOCommand::AtGetServices => "AT+GETSERVICES".to_string(),
+            BleuIOCommand::AtGattcRead(handle) => format!("AT+GATTCREAD={handle:04X}"),
+            BleuIOCommand::AtAutoExecList => "AT+AUTOEXEC".to_string(),
+            BleuIOCommand::AtAutoExecAdd(cmd) => format!("AT+AUTOEXEC={cmd}"),
+            BleuIOCommand::AtClrAutoExec => "AT+CLRAUTOEXEC".to_string(),
+        }
+    }
+
+    // Return the exact string sent to the dongle, including line terminator.
+    // Ctrl-C is a bare control character and has no terminator.
+    pub fn to_wire(&self) -> String {
+        match self {
+            BleuIOCommand::StopScan => self.to_command_string(),
+            _ => format!("{}\r\n", self.to_command_string()),
+        }
+    }
+
+    // Return false for commands the dongle doesn't answer with a C/A/E sequence.
+    pub fn expects_response(&self) -> bool {
+        !matches!(self, BleuIOCommand::StopScan | BleuIOCommand::Atr)
+    }
 }
 
 #[derive(Debug,PartialEq)]
@@ -27,7 +286,7 @@ pub enum BleuIOResponseType {
     EventResponse, 	            // {event code:"Connection Index in hex if any otherwise 0xFFFF",Event response data}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BleuIOErrorCode {
 	Success,                        // 0x00
 	GenericFailure,                 // 0x01
@@ -48,11 +307,65 @@ pub enum BleuIOErrorCode {
 	NoCreditsAvailableOnL2CAPCoC,   // 0x10
 	MTUExceededOnL2CAPCoC,          // 0x11
 	InsufficientBandwidth,          // 0x12
-    UnknownError,
+    Unknown(i64),                   // a code not in the table, as the dongle sent it
+}
+
+// An "err" value in an acknowledgement that isn't in the BleuIO error code table.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnknownErrorCode(pub i64);
+
+impl std::fmt::Display for UnknownErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown BleuIO error code 0x{:02X}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownErrorCode {}
+
+impl BleuIOErrorCode {
+    // Return the code as sent by the dongle.
+    pub fn get_code(&self) -> i64 {
+        match self {
+            BleuIOErrorCode::Success => 0x00,
+            BleuIOErrorCode::GenericFailure => 0x01,
+            BleuIOErrorCode::AlreadyDone => 0x02,
+            BleuIOErrorCode::OperationAlreadyInProgress => 0x03,
+            BleuIOErrorCode::InvalidParameter => 0x04,
+            BleuIOErrorCode::NotAllowed => 0x05,
+            BleuIOErrorCode::NotConnected => 0x06,
+            BleuIOErrorCode::NotSupported => 0x07,
+            BleuIOErrorCode::NotAccepted => 0x08,
+            BleuIOErrorCode::Busy => 0x09,
+            BleuIOErrorCode::RequestTimedOut => 0x0A,
+            BleuIOErrorCode::NotSupportedByPeer => 0x0B,
+            BleuIOErrorCode::CanceledByUser => 0x0C,
+            BleuIOErrorCode::EncryptionKeyMissing => 0x0D,
+            BleuIOErrorCode::InsufficientResources => 0x0E,
+            BleuIOErrorCode::NotFound => 0x0F,
+            BleuIOErrorCode::NoCreditsAvailableOnL2CAPCoC => 0x10,
+            BleuIOErrorCode::MTUExceededOnL2CAPCoC => 0x11,
+            BleuIOErrorCode::InsufficientBandwidth => 0x12,
+            BleuIOErrorCode::Unknown(code) => *code,
+        }
+    }
+
+    // Return true for errors that mean "try again later" rather than "this won't work".
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, BleuIOErrorCode::Busy | BleuIOErrorCode::RequestTimedOut)
+    }
+}
+
+impl std::fmt::Display for BleuIOErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BleuIOErrorCode::Unknown(code) => write!(f, "Unknown error code {code} (0x{code:02X})"),
+            code => write!(f, "{code:?} (0x{:02X})", code.get_code()),
+        }
+    }
 }
 
 impl TryFrom<i64> for BleuIOErrorCode {
-    type Error = &'static str;
+    type Error = UnknownErrorCode;
 
     fn try_from(value: i64) -> std::result::Result<Self, Self::Error> {
         match value {
@@ -75,36 +388,132 @@ impl TryFrom<i64> for BleuIOErrorCode {
             0x10 => Ok(BleuIOErrorCode::NoCreditsAvailableOnL2CAPCoC),
             0x11 => Ok(BleuIOErrorCode::MTUExceededOnL2CAPCoC),
             0x12 => Ok(BleuIOErrorCode::InsufficientBandwidth),
-            _ => Err("fel"),
+            _ => Err(UnknownErrorCode(value)),
         }
     }
 }
 
-// Checks if given UsbPortInfo is associated with an attached BleuIO device.
-fn is_bleuio(info: &UsbPortInfo) -> bool {
-    (info.vid == BLUEIO_VID) && (info.pid == BLUEIO_PID)
+// Return the model of the BleuIO dongle behind a USB port, None if it isn't one. `extra` are
+// the VID/PID pairs from the configuration; they take precedence over the built-in table.
+fn bleuio_model(info: &UsbPortInfo, extra: &[BleuIOVariant]) -> Option<BleuIOModel> {
+    extra
+        .iter()
+        .chain(KNOWN_VARIANTS.iter())
+        .find(|v| v.vid == info.vid && v.pid == info.pid)
+        .map(|v| v.model)
 }
 
-// Scan list of available USB devices and return device path of first detected BleuIO device.
-pub fn find_bleuio() -> String {
-        serialport5::available_ports()
-        .unwrap_or_default()
+// An attached BleuIO dongle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BleuIOPort {
+    pub port_name: String,                  // device path to open
+    pub serial_number: Option<String>,      // USB serial number, stable across replugs
+    pub model: BleuIOModel,
+}
+
+// Return the path to open for a serial port. On macOS the call-out device must be used.
+fn device_path(port_name: String) -> String {
+    #[cfg(target_os = "macos")]
+    return port_name.replace("/dev/tty.", "/dev/cu.");
+
+    #[cfg(not(target_os = "macos"))]
+    return port_name;
+}
+
+// Scan list of available USB devices and return all detected BleuIO devices, recognising
+// the `extra` VID/PID pairs too.
+pub fn find_bleuio_all(extra: &[BleuIOVariant]) -> Vec<BleuIOPort> {
+    bleuio_ports(serialport5::available_ports().unwrap_or_default(), extra)
+}
+
+// Return the BleuIO dongles among the serial ports of the system.
+fn bleuio_ports(ports: Vec<SerialPortInfo>, extra: &[BleuIOVariant]) -> Vec<BleuIOPort> {
+    ports
         .into_iter()
-        .find_map(|p| {
-            if let SerialPortType::UsbPort(info) = p.port_type {
-                if is_bleuio(&info) {
-                    #[cfg(target_os = "macos")]
-                    return Some(p.port_name.replace("/dev/tty.", "/dev/cu."));
-                    
-                    #[cfg(not(target_os = "macos"))]
-                    return Some(p.port_name);
-                }
+        .filter_map(|p| match p.port_type {
+            SerialPortType::UsbPort(info) => bleuio_model(&info, extra).map(|model| BleuIOPort {
+                port_name: device_path(p.port_name),
+                serial_number: info.serial_number,
+                model,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+// A serial port offered to the user when picking a dongle by hand.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PortCandidate {
+    pub port_name: String,
+    pub usb: Option<UsbPortInfo>,       // None for non-USB ports
+    pub model: Option<BleuIOModel>,     // None if it isn't a BleuIO dongle
+}
+
+impl PortCandidate {
+    // Return "VID:PID" in hex, or an empty string for non-USB ports.
+    pub fn get_vid_pid_string(&self) -> String {
+        self.usb
+            .as_ref()
+            .map(|info| format!("{:04X}:{:04X}", info.vid, info.pid))
+            .unwrap_or_default()
+    }
+
+    pub fn is_bleuio(&self) -> bool {
+        self.model.is_some()
+    }
+}
+
+// List every serial port on the system, BleuIO dongles first.
+pub fn list_serial_ports(extra: &[BleuIOVariant]) -> Vec<PortCandidate> {
+    port_candidates(serialport5::available_ports().unwrap_or_default(), extra)
+}
+
+fn port_candidates(ports: Vec<SerialPortInfo>, extra: &[BleuIOVariant]) -> Vec<PortCandidate> {
+    let mut ports: Vec<PortCandidate> = ports
+        .into_iter()
+        .map(|p| {
+            let usb = match p.port_type {
+                SerialPortType::UsbPort(info) => Some(info),
+                _ => None,
+            };
+            PortCandidate {
+                port_name: device_path(p.port_name),
+                model: usb.as_ref().and_then(|info| bleuio_model(info, extra)),
+                usb,
             }
-            None
         })
+        .collect();
+    ports.sort_by_key(|p| !p.is_bleuio());
+    ports
+}
+
+// Return the model of the dongle at `port_name`, None if no BleuIO dongle is attached there.
+pub fn find_bleuio_model(port_name: &str, extra: &[BleuIOVariant]) -> Option<BleuIOModel> {
+    find_bleuio_all(extra).into_iter().find(|p| p.port_name == port_name).map(|p| p.model)
+}
+
+// Scan list of available USB devices and return device path of first detected BleuIO device.
+pub fn find_bleuio() -> String {
+    find_bleuio_all(&[])
+        .into_iter()
+        .next()
+        .map(|p| p.port_name)
         .unwrap_or_default()
 }
 
+// Return the device path of the BleuIO dongle with the given USB serial number.
+pub fn find_bleuio_by_serial(serial_number: &str, extra: &[BleuIOVariant]) -> Option<String> {
+    find_bleuio_all(extra)
+        .into_iter()
+        .find(|p| p.serial_number.as_deref() == Some(serial_number))
+        .map(|p| p.port_name)
+}
+
+// A single line of BleuIO verbose (ATV1) output, decoded into its typed payload.
+//
+// Every response carries the index of the command it belongs to, except events
+// which are keyed by their numeric event code.
+//
 /* Test result strings:
     {"C":38,"cmd":"AT+FINDSCANDATA=FF5B07=2"}
     {"A":38,"err":0,"errMsg":"ok"}
@@ -114,35 +523,437 @@ pub fn find_bleuio() -> String {
     {"SF":38,"addr":"D2:B1:28:3F:42:D4","type":0,"data":"0201061BFF5B070504220049880B7F27EE00AB000A01000000000000024503"}
     {"SE":38,"action":"scan completed"}
 */
-pub fn get_bleuio_result_type(v: &Value) -> BleuIOResponseType {
-    let result_type: BleuIOResponseType;
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum BleuIOResponse {
+    Command {
+        #[serde(rename = "C")]
+        idx: u32,
+        cmd: String,
+    },
+    Acknowledgement {
+        #[serde(rename = "A")]
+        idx: u32,
+        #[serde(deserialize_with = "deserialize_error_code")]
+        err: BleuIOErrorCode,
+        #[serde(rename = "errMsg", default)]
+        err_msg: String,
+    },
+    Reply {
+        #[serde(rename = "R")]
+        idx: u32,
+        #[serde(flatten)]
+        data: Map<String, Value>,
+    },
+    End {
+        #[serde(rename = "E")]
+        idx: u32,
+        nol: u32,
+    },
+    ScanData {
+        #[serde(rename = "S")]
+        idx: u32,
+        rssi: Option<i32>,
+        addr: String,
+        name: Option<String>,
+    },
+    ScanFindData {
+        #[serde(rename = "SF")]
+        idx: u32,
+        rssi: Option<i32>,
+        addr: String,
+        #[serde(rename = "type")]
+        adv_type: u8,
+        data: String,
+    },
+    ScanTarget {
+        #[serde(rename = "ST")]
+        idx: u32,
+        rssi: Option<i32>,
+        addr: String,
+        #[serde(rename = "type")]
+        adv_type: u8,
+        data: String,
+    },
+    ScanEnded {
+        #[serde(rename = "SE")]
+        idx: u32,
+        #[serde(default)]
+        action: String,
+    },
+    Event(BleuIOEvent),
+    Unknown(Value),
+    // Plain text line, e.g. "ECHO OFF" or "VERBOSE ON" when the dongle isn't in verbose mode.
+    #[serde(skip)]
+    Text(String),
+}
+
+// Event line, e.g. {"779":"0x0000","evt":{"action":"connected"}}.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BleuIOEvent {
+    pub code: u32,                  // numeric event code (the JSON key)
+    pub conn_idx: Option<u16>,      // connection index, None when the dongle reports 0xFFFF
+    pub data: Map<String, Value>,   // remaining event response data
+}
+
+impl<'de> Deserialize<'de> for BleuIOEvent {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut data = Map::deserialize(deserializer)?;
+        let code_key = data
+            .keys()
+            .find(|k| k.parse::<u32>().is_ok())
+            .cloned()
+            .ok_or_else(|| D::Error::custom("no numeric event code"))?;
+        let conn = data.remove(&code_key).unwrap_or(Value::Null);
+        let conn_idx = conn
+            .as_str()
+            .and_then(|s| u16::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+            .filter(|idx| *idx != 0xFFFF);
 
-    if v.get("C").is_some() {
-        result_type = BleuIOResponseType::CommandResponse;
-    } else if v.get("A").is_some() {
-        result_type = BleuIOResponseType::AcknowledgementResponse;
-    } else if v.get("R").is_some() {
-        result_type = BleuIOResponseType::ReplyResponse;
-    } else if v.get("E").is_some() {
-        result_type = BleuIOResponseType::EndResponse;
-    } else if v.get("S").is_some() {
-        result_type = BleuIOResponseType::ScanDataResponse;
-    } else if v.get("SF").is_some() {
-        result_type = BleuIOResponseType::ScanFindDataResponse;
-    } else if v.get("ST").is_some() {
-        result_type = BleuIOResponseType::ScanTargetResponse;
-    } else if v.get("SE").is_some() {
-        result_type = BleuIOResponseType::ScanEndedResponse;
-    } else {
-        result_type = BleuIOResponseType::UnknownResponse;
+        Ok(BleuIOEvent {
+            code: code_key.parse().unwrap_or_default(),
+            conn_idx,
+            data,
+        })
     }
+}
 
-    result_type
+// Connection events reported while acting as central.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    Connected { conn_idx: Option<u16>, addr: Option<String> },
+    Disconnected { conn_idx: Option<u16>, reason: Option<String> },
+}
+
+impl BleuIOEvent {
+    // Return the connection event this line reports, if it is one. The event codes differ
+    // between firmware versions, so the "action" is what counts.
+    pub fn get_connection_event(&self) -> Option<ConnectionEvent> {
+        let evt = self.data.get("evt")?;
+        let field = |name: &str| match evt.get(name)? {
+            Value::String(s) => Some(s.clone()),
+            Value::Null => None,
+            other => Some(other.to_string()),
+        };
+        match evt.get("action")?.as_str()? {
+            "connected" => Some(ConnectionEvent::Connected { conn_idx: self.conn_idx, addr: field("addr") }),
+            "disconnected" => Some(ConnectionEvent::Disconnected { conn_idx: self.conn_idx, reason: field("reason") }),
+            _ => None,
+        }
+    }
 }
 
-pub fn parse_bleuio_result(json: &str) -> Result<Value> {
-    let v: Value = serde_json::from_str(json)?; 
-    Ok(v)
+// The "err" field is normally a number but the protocol documents it as hex, so accept "0x0A" too.
+// Codes we don't know about are kept as Unknown instead of failing the whole line; -1 if the
+// field isn't a number at all.
+fn deserialize_error_code<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<BleuIOErrorCode, D::Error> {
+    let code = match Value::deserialize(deserializer)? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => i64::from_str_radix(s.trim_start_matches("0x"), 16).ok(),
+        _ => None,
+    };
+    let code = code.unwrap_or(-1);
+    Ok(BleuIOErrorCode::try_from(code).unwrap_or(BleuIOErrorCode::Unknown(code)))
+}
+
+impl BleuIOResponse {
+    // Return the kind of response this line is.
+    pub fn get_type(&self) -> BleuIOResponseType {
+        match self {
+            BleuIOResponse::Command { .. } => BleuIOResponseType::CommandResponse,
+            BleuIOResponse::Acknowledgement { .. } => BleuIOResponseType::AcknowledgementResponse,
+            BleuIOResponse::Reply { .. } => BleuIOResponseType::ReplyResponse,
+            BleuIOResponse::End { .. } => BleuIOResponseType::EndResponse,
+            BleuIOResponse::ScanData { .. } => BleuIOResponseType::ScanDataResponse,
+            BleuIOResponse::ScanFindData { .. } => BleuIOResponseType::ScanFindDataResponse,
+            BleuIOResponse::ScanTarget { .. } => BleuIOResponseType::ScanTargetResponse,
+            BleuIOResponse::ScanEnded { .. } => BleuIOResponseType::ScanEndedResponse,
+            BleuIOResponse::Event(_) => BleuIOResponseType::EventResponse,
+            BleuIOResponse::Unknown(_) | BleuIOResponse::Text(_) => BleuIOResponseType::UnknownResponse,
+        }
+    }
+
+    // Return the command index this line belongs to, if it has one.
+    pub fn get_index(&self) -> Option<u32> {
+        match self {
+            BleuIOResponse::Command { idx, .. }
+            | BleuIOResponse::Acknowledgement { idx, .. }
+            | BleuIOResponse::Reply { idx, .. }
+            | BleuIOResponse::End { idx, .. }
+            | BleuIOResponse::ScanData { idx, .. }
+            | BleuIOResponse::ScanFindData { idx, .. }
+            | BleuIOResponse::ScanTarget { idx, .. }
+            | BleuIOResponse::ScanEnded { idx, .. } => Some(*idx),
+            BleuIOResponse::Event(_) | BleuIOResponse::Unknown(_) | BleuIOResponse::Text(_) => None,
+        }
+    }
+
+    // Return true for lines produced by an ongoing scan rather than as a reply to a command.
+    pub fn is_scan_result(&self) -> bool {
+        matches!(
+            self,
+            BleuIOResponse::ScanData { .. }
+                | BleuIOResponse::ScanFindData { .. }
+                | BleuIOResponse::ScanTarget { .. }
+                | BleuIOResponse::ScanEnded { .. }
+        )
+    }
+}
+
+// Parse one line of BleuIO verbose output. Fails only if the line isn't a JSON object.
+pub fn parse_bleuio_response(line: &str) -> Result<BleuIOResponse> {
+    let v: BleuIOResponse = serde_json::from_str(line)?;
+    match v {
+        BleuIOResponse::Unknown(ref value) if !value.is_object() => {
+            Err(serde_json::Error::custom("BleuIO response is not a JSON object"))
+        }
+        _ => Ok(v),
+    }
+}
+
+// Parse one line of BleuIO output. Lines that aren't JSON objects are returned as Text.
+pub fn parse_bleuio_line(line: &str) -> BleuIOResponse {
+    parse_bleuio_response(line)
+        .ok()
+        .or_else(|| parse_plain_scan_line(line))
+        .unwrap_or_else(|| BleuIOResponse::Text(line.to_string()))
+}
+
+// Return the number after "RSSI:" in a plain text line.
+fn parse_plain_rssi(text: &str) -> Option<i32> {
+    let (_, rest) = text.split_once("RSSI:")?;
+    let rest = rest.trim_start();
+    let end = rest
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || (*i == 0 && *c == '-')))
+        .map(|(i, _)| i)
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+// Parse scan output in non-verbose mode (ATV0). These lines carry no command index, so idx is 0.
+//   [F5:50:35:CF:B1:ED] Device Data [ADV]: 0201061BFF5B07...     (AT+FINDSCANDATA, AT+SCANTARGET)
+//   [F5:50:35:CF:B1:ED] RSSI: -61 Device Data [SR]: 0201...       (with AT+SHOWRSSI=1)
+//   [01] Device: [1]D2:B1:28:3F:42:D4  RSSI: -74 (HibouAir)       (AT+GAPSCAN)
+//   SCAN COMPLETE
+pub fn parse_plain_scan_line(line: &str) -> Option<BleuIOResponse> {
+    let line = line.trim();
+    if line == "SCAN COMPLETE" {
+        return Some(BleuIOResponse::ScanEnded { idx: 0, action: "scan completed".to_string() });
+    }
+
+    if let Some((head, data)) = line.split_once("Device Data [") {
+        let addr = head.strip_prefix('[')?.split(']').next()?;
+        BleAddress::new(BleAddressType::Public, addr).ok()?;
+        let (kind, hex_data) = data.split_once("]:")?;
+        let hex_data = hex_data.trim();
+        hex::decode(hex_data).ok()?;
+        return Some(BleuIOResponse::ScanFindData {
+            idx: 0,
+            rssi: parse_plain_rssi(head),
+            addr: addr.to_string(),
+            adv_type: if kind == "SR" { 4 } else { 0 },
+            data: hex_data.to_string(),
+        });
+    }
+
+    if let Some((_, rest)) = line.split_once("Device:") {
+        let addr: BleAddress = rest.split_whitespace().next()?.parse().ok()?;
+        let name = rest
+            .rsplit_once('(')
+            .and_then(|(_, name)| name.strip_suffix(')'))
+            .map(|name| name.to_string());
+        return Some(BleuIOResponse::ScanData { idx: 0, rssi: parse_plain_rssi(rest), addr: addr.mac, name });
+    }
+
+    None
+}
+
+// How a dongle currently formats its output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DongleMode {
+    pub echo: bool,         // commands are echoed back (ATE1)
+    pub verbose: bool,      // replies are JSON (ATV1) instead of plain text
+}
+
+// Firmware version as reported by ATI, e.g. "2.7.5".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FirmwareVersion(pub u32, pub u32, pub u32);
+
+impl std::str::FromStr for FirmwareVersion {
+    type Err = String;
+
+    // Accepts "2.7.5", "v2.7.5" and "2.7" (patch 0).
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts: Vec<u32> = s
+            .trim()
+            .trim_start_matches(['v', 'V'])
+            .split('.')
+            .map(|p| p.parse::<u32>())
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| format!("Invalid firmware version: {s}"))?;
+        match parts[..] {
+            [major, minor] => Ok(FirmwareVersion(major, minor, 0)),
+            [major, minor, patch] => Ok(FirmwareVersion(major, minor, patch)),
+            _ => Err(format!("Invalid firmware version: {s}")),
+        }
+    }
+}
+
+impl std::fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.0, self.1, self.2)
+    }
+}
+
+// GAP role the dongle is in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GapRole {
+    Central,
+    Peripheral,
+    Dual,
+    #[default]
+    Unknown,
+}
+
+impl GapRole {
+    fn from_text(text: &str) -> Self {
+        let text = text.to_lowercase();
+        if text.contains("dual") {
+            GapRole::Dual
+        } else if text.contains("central") {
+            GapRole::Central
+        } else if text.contains("peripheral") {
+            GapRole::Peripheral
+        } else {
+            GapRole::Unknown
+        }
+    }
+}
+
+// What ATI and AT+GETMAC tell about a dongle.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BleuIODeviceInfo {
+    pub firmware: String,
+    pub hardware: String,
+    pub role: GapRole,
+    pub connected: bool,
+    pub advertising: bool,
+    pub mac: Option<String>,
+    pub model: Option<BleuIOModel>,     // from the USB ids, None when not attached by USB
+}
+
+impl BleuIODeviceInfo {
+    // Build from the reply to ATI. Both the verbose (JSON) and the plain text form are understood:
+    //   {"R":1,"dev":"Smart Sensor Devices","hw":"DA14683","fwVer":"2.7.5","gap role":"dual"}
+    //   {"R":1,"connected":false,"advertising":false}
+    // or
+    //   Smart Sensor Devices / DA14683 / BleuIO Firmware Version: 2.7.5 / Dual role / Not Connected / Not Advertising
+    pub fn from_ati(lines: &[BleuIOResponse]) -> Self {
+        let mut info = BleuIODeviceInfo::default();
+        for line in lines {
+            match line {
+                BleuIOResponse::Reply { data, .. } => {
+                    if let Some(fw) = data.get("fwVer").and_then(Value::as_str) {
+                        info.firmware = fw.to_string();
+                    }
+                    if let Some(hw) = data.get("hw").and_then(Value::as_str) {
+                        info.hardware = hw.to_string();
+                    }
+                    if let Some(role) = data.get("gap role").and_then(Value::as_str) {
+                        info.role = GapRole::from_text(role);
+                    }
+                    if let Some(connected) = data.get("connected").and_then(Value::as_bool) {
+                        info.connected = connected;
+                    }
+                    if let Some(advertising) = data.get("advertising").and_then(Value::as_bool) {
+                        info.advertising = advertising;
+                    }
+                }
+                BleuIOResponse::Text(text) => {
+                    let text = text.trim();
+                    if let Some((_, fw)) = text.split_once("Firmware Version:") {
+                        info.firmware = fw.trim().to_string();
+                    } else if text.ends_with("role") {
+                        info.role = GapRole::from_text(text);
+                    } else if text.starts_with("DA14") {
+                        info.hardware = text.to_string();
+                    } else if text.ends_with("Connected") {
+                        info.connected = !text.starts_with("Not");
+                    } else if text.ends_with("Advertising") {
+                        info.advertising = !text.starts_with("Not");
+                    }
+                }
+                _ => {}
+            }
+        }
+        info
+    }
+
+    // Return the firmware version, if ATI reported one that can be parsed.
+    pub fn get_firmware_version(&self) -> Option<FirmwareVersion> {
+        self.firmware.parse().ok()
+    }
+
+    // Return true if the firmware is known to be older than `min`.
+    pub fn is_firmware_older_than(&self, min: FirmwareVersion) -> bool {
+        self.get_firmware_version().is_some_and(|v| v < min)
+    }
+}
+
+// Find the dongle's own MAC address in the reply to AT+GETMAC.
+pub fn parse_mac(lines: &[BleuIOResponse]) -> Option<String> {
+    let is_mac = |s: &str| BleAddress::new(BleAddressType::Public, s.trim()).is_ok();
+    lines.iter().find_map(|line| match line {
+        BleuIOResponse::Reply { data, .. } => data
+            .values()
+            .filter_map(Value::as_str)
+            .find(|s| is_mac(s))
+            .map(|s| s.trim().to_uppercase()),
+        BleuIOResponse::Text(text) if is_mac(text) => Some(text.trim().to_uppercase()),
+        _ => None,
+    })
+}
+
+// Errors reported while talking to a BleuIO dongle.
+#[derive(Debug)]
+pub enum BleuIOError {
+    Io(std::io::Error),             // serial port could not be opened, read or written
+    Timeout,                        // no complete response within the allowed time
+    Closed,                         // the port was closed while waiting for a response
+    Command(BleuIOErrorCode),       // the dongle acknowledged the command with an error code
+    Reset(u32),                     // the dongle was reset after this many commands failed in a row
+}
+
+impl BleuIOError {
+    // Return true if sending the same command again may succeed.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            BleuIOError::Timeout => true,
+            BleuIOError::Command(code) => code.is_retryable(),
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Display for BleuIOError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BleuIOError::Io(e) => write!(f, "Serial port error: {e}"),
+            BleuIOError::Timeout => write!(f, "Timed out waiting for response"),
+            BleuIOError::Closed => write!(f, "Serial port closed"),
+            BleuIOError::Command(code) => write!(f, "Command failed: {code}"),
+            BleuIOError::Reset(failures) => write!(f, "Dongle reset after {failures} failed commands"),
+        }
+    }
+}
+
+impl std::error::Error for BleuIOError {}
+
+impl From<std::io::Error> for BleuIOError {
+    fn from(e: std::io::Error) -> Self {
+        BleuIOError::Io(e)
+    }
 }
 
 #[cfg(test)]
@@ -164,6 +975,71 @@ mod tests {
             assert!(path.is_empty() || path.starts_with("COM"));
     }
 
+    // Ports as the system lists them: two dongles, a foreign USB device and a built-in port.
+    fn system_ports() -> Vec<SerialPortInfo> {
+        let usb = |vid, pid, serial_number: Option<&str>| SerialPortType::UsbPort(UsbPortInfo {
+            vid,
+            pid,
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: None,
+            product: None,
+        });
+        vec![
+            SerialPortInfo { port_name: "/dev/ttyS0".to_string(), port_type: SerialPortType::Unknown },
+            SerialPortInfo { port_name: "/dev/ttyUSB0".to_string(), port_type: usb(0x1234, 0x5678, Some("FTDI1")) },
+            SerialPortInfo { port_name: "/dev/ttyACM0".to_string(), port_type: usb(BLUEIO_VID, BLUEIO_PID, Some("4048FDE6")) },
+            SerialPortInfo { port_name: "/dev/ttyACM1".to_string(), port_type: usb(BLUEIO_VID, BLUEIO_PID, None) },
+        ]
+    }
+
+    #[test]
+    fn test_bleuio_ports() {
+        assert_eq!(bleuio_ports(system_ports(), &[]), vec![
+            BleuIOPort {
+                port_name: "/dev/ttyACM0".to_string(),
+                serial_number: Some("4048FDE6".to_string()),
+                model: BleuIOModel::BleuIO,
+            },
+            BleuIOPort { port_name: "/dev/ttyACM1".to_string(), serial_number: None, model: BleuIOModel::BleuIO },
+        ]);
+        assert!(bleuio_ports(Vec::new(), &[]).is_empty());
+
+        // A USB id from the configuration only counts where it is passed in.
+        let extra = ["1234:5678".parse().unwrap()];
+        let ports = bleuio_ports(system_ports(), &extra);
+        assert_eq!(ports.len(), 3);
+        assert_eq!(ports[0].port_name, "/dev/ttyUSB0");
+        assert_eq!(ports[0].model, BleuIOModel::Other);
+    }
+
+    #[test]
+    fn test_port_candidates() {
+        let ports = port_candidates(system_ports(), &[]);
+        let names: Vec<&str> = ports.iter().map(|p| p.port_name.as_str()).collect();
+        // BleuIO dongles first, the rest in the order the system lists them.
+        assert_eq!(names, ["/dev/ttyACM0", "/dev/ttyACM1", "/dev/ttyS0", "/dev/ttyUSB0"]);
+        assert_eq!(ports.iter().filter(|p| p.is_bleuio()).count(), 2);
+        assert_eq!(ports[2].get_vid_pid_string(), "");
+        assert_eq!(ports[3].get_vid_pid_string(), "1234:5678");
+    }
+
+    #[test]
+    fn test_port_candidate_vid_pid() {
+        let candidate = PortCandidate {
+            port_name: "/dev/ttyACM0".to_string(),
+            usb: Some(UsbPortInfo {
+                vid: BLUEIO_VID,
+                pid: BLUEIO_PID,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            }),
+            model: Some(BleuIOModel::BleuIO),
+        };
+        assert_eq!(candidate.get_vid_pid_string(), "2DCF:6002");
+        assert_eq!(PortCandidate { usb: None, ..candidate }.get_vid_pid_string(), "");
+    }
+
     #[test]
     fn test_is_bleuio() {
         let info = UsbPortInfo {
@@ -173,7 +1049,7 @@ mod tests {
             manufacturer: None,
             product: None,
         };
-        assert!(is_bleuio(&info));
+        assert_eq!(bleuio_model(&info, &[]), Some(BleuIOModel::BleuIO));
 
         let wrong_info = UsbPortInfo {
             vid: 0x1234,
@@ -182,49 +1058,418 @@ mod tests {
             manufacturer: None,
             product: None,
         };
-        assert!(!is_bleuio(&wrong_info));
+        assert_eq!(bleuio_model(&wrong_info, &[]), None);
+    }
+
+    #[test]
+    fn test_bleuio_models() {
+        let usb = |vid, pid| UsbPortInfo { vid, pid, serial_number: None, manufacturer: None, product: None };
+        assert_eq!(bleuio_model(&usb(0x2DCF, 0x6002), &[]), Some(BleuIOModel::BleuIO));
+        assert_eq!(bleuio_model(&usb(0x2DCF, 0x6001), &[]), None);
+        assert_eq!(bleuio_model(&usb(0x1209, 0xB10E), &[]), None);
+
+        let extra: Vec<BleuIOVariant> = vec!["1209:b10e".parse().unwrap(), "0x2DCF:0x6001=pro".parse().unwrap()];
+        assert_eq!(bleuio_model(&usb(0x1209, 0xB10E), &extra), Some(BleuIOModel::Other));
+        assert_eq!(bleuio_model(&usb(0x2DCF, 0x6001), &extra), Some(BleuIOModel::BleuIOPro));
+        assert_eq!(bleuio_model(&usb(0x2DCF, 0x6002), &extra), Some(BleuIOModel::BleuIO));
     }
 
     #[test]
-    fn test_parse_bleuio_result() {
+    fn test_parse_variant() {
+        assert_eq!(
+            "2dcf:6002".parse::<BleuIOVariant>(),
+            Ok(BleuIOVariant { vid: 0x2DCF, pid: 0x6002, model: BleuIOModel::Other })
+        );
+        assert_eq!("2DCF:6001=pro".parse::<BleuIOVariant>().unwrap().model, BleuIOModel::BleuIOPro);
+        assert_eq!("2dcf:6002=BleuIO".parse::<BleuIOVariant>().unwrap().model, BleuIOModel::BleuIO);
+        assert!("2dcf".parse::<BleuIOVariant>().is_err());
+        assert!("2dcf:xyz".parse::<BleuIOVariant>().is_err());
+        assert!("2dcf:6002=dongle".parse::<BleuIOVariant>().is_err());
+    }
+
+    #[test]
+    fn test_model_capabilities() {
+        assert_eq!(Capabilities::default(), BleuIOModel::BleuIO.get_capabilities());
+        assert!(BleuIOModel::BleuIOPro.get_capabilities().gatt_client);
+        let other = BleuIOModel::Other.get_capabilities();
+        assert!(other.show_rssi && !other.autoexec && !other.gatt_client);
+        assert_eq!("Pro".parse::<BleuIOModel>(), Ok(BleuIOModel::BleuIOPro));
+    }
+
+    #[test]
+    fn test_parse_bleuio_response() {
         let json = r#"{"C":38,"cmd":"AT+FINDSCANDATA=FF5B07=2"}"#;
-        let result = parse_bleuio_result(json);
+        let result = parse_bleuio_response(json);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_parse_bleuio_response_not_json() {
+        assert!(parse_bleuio_response("ECHO OFF").is_err());
+        assert!(parse_bleuio_response("{\"A\":38,").is_err());
+        assert!(parse_bleuio_response("42").is_err());
+    }
+
+    #[test]
+    fn test_parse_bleuio_line() {
+        assert_eq!(parse_bleuio_line("ECHO OFF"), BleuIOResponse::Text("ECHO OFF".to_string()));
+        assert_eq!(parse_bleuio_line(r#"{"E":1,"nol":3}"#), BleuIOResponse::End { idx: 1, nol: 3 });
+    }
+
+    #[test]
+    fn test_command_to_wire() {
+        assert_eq!(BleuIOCommand::Ate0.to_wire(), "ATE0\r\n");
+        assert_eq!(BleuIOCommand::Atv1.to_wire(), "ATV1\r\n");
+        assert_eq!(BleuIOCommand::hibouair_scan().to_wire(), "AT+FINDSCANDATA=FF5B07\r\n");
+        assert_eq!(BleuIOCommand::StopScan.to_wire(), "\x03");
+        assert!(!BleuIOCommand::StopScan.expects_response());
+        assert!(BleuIOCommand::AtI.expects_response());
+        assert!(BleuIOCommand::hibouair_scan().starts_scan());
+        assert!(!BleuIOCommand::StopScan.starts_scan());
+    }
+
+    #[test]
+    fn test_command_strings() {
+        let target: BleAddress = "[1]d2:b1:28:3f:42:d4".parse().unwrap();
+        let cases = [
+            (BleuIOCommand::Atr, "ATR"),
+            (BleuIOCommand::AtDual, "AT+DUAL"),
+            (BleuIOCommand::AtShowRssi(true), "AT+SHOWRSSI=1"),
+            (BleuIOCommand::AtShowRssi(false), "AT+SHOWRSSI=0"),
+            (BleuIOCommand::AtGetMac, "AT+GETMAC"),
+            (BleuIOCommand::AtGapScan(None), "AT+GAPSCAN"),
+            (BleuIOCommand::AtGapScan(Some(10)), "AT+GAPSCAN=10"),
+            (BleuIOCommand::AtFindscandata { pattern: "ff5b07".to_string(), duration: Some(2) }, "AT+FINDSCANDATA=FF5B07=2"),
+            (
+                BleuIOCommand::AtScanTarget {
+                    targets: vec![target.clone(), "[0]00:11:22:33:44:55".parse().unwrap()],
+                    duration: Some(5),
+                },
+                "AT+SCANTARGET=[1]D2:B1:28:3F:42:D4;[0]00:11:22:33:44:55=5",
+            ),
+            (
+                BleuIOCommand::AtScanParam(ScanParams { active: true, interval: 160, window: 80, filter_duplicates: false }),
+                "AT+SCANPARAM=1=160=80=0",
+            ),
+            (BleuIOCommand::AtScanFilter(ScanFilter::Uuid("180d".to_string())), "AT+SCANFILTER=UUID=180D"),
+            (BleuIOCommand::AtScanFilter(ScanFilter::Name("HibouAir".to_string())), "AT+SCANFILTER=NAME=HibouAir"),
+            (BleuIOCommand::AtScanFilter(ScanFilter::Data("5b07".to_string())), "AT+SCANFILTER=DATA=5B07"),
+            (BleuIOCommand::AtScanFilter(ScanFilter::Clear), "AT+SCANFILTER=CLEAR"),
+            (BleuIOCommand::AtScanFilter(ScanFilter::Show), "AT+SCANFILTER=?"),
+            (BleuIOCommand::AtCancelConnect, "AT+CANCELCONNECT"),
+            (BleuIOCommand::AtGapConnect(target), "AT+GAPCONNECT=[1]D2:B1:28:3F:42:D4"),
+            (BleuIOCommand::AtGapDisconnect, "AT+GAPDISCONNECT"),
+            (BleuIOCommand::AtGapDisconnectAll, "AT+GAPDISCONNECTALL"),
+            (BleuIOCommand::AtGetServices, "AT+GETSERVICES"),
+            (BleuIOCommand::AtGattcRead(0x1B), "AT+GATTCREAD=001B"),
+            (BleuIOCommand::AtAutoExecList, "AT+AUTOEXEC"),
+            (BleuIOCommand::AtAutoExecAdd("AT+SHOWRSSI=1".to_string()), "AT+AUTOEXEC=AT+SHOWRSSI=1"),
+            (BleuIOCommand::AtClrAutoExec, "AT+CLRAUTOEXEC"),
+        ];
+        for (cmd, expected) in cases {
+            assert_eq!(cmd.to_command_string(), expected);
+            assert_eq!(cmd.to_wire(), format!("{expected}\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_ble_address() {
+        let addr: BleAddress = "F5:50:35:CF:B1:ED".parse().unwrap();
+        assert_eq!(addr.addr_type, BleAddressType::Random);
+        assert_eq!(addr.to_string(), "[1]F5:50:35:CF:B1:ED");
+
+        let addr: BleAddress = "[0]00:11:22:33:44:55".parse().unwrap();
+        assert_eq!(addr.addr_type, BleAddressType::Public);
+
+        assert!("F5:50:35:CF:B1".parse::<BleAddress>().is_err());
+        assert!("F5:50:35:CF:B1:XX".parse::<BleAddress>().is_err());
+        assert!("[2]F5:50:35:CF:B1:ED".parse::<BleAddress>().is_err());
+    }
+
     #[test]
     fn test_get_bleuio_result_type() {
         let json = r#"{"C":38,"cmd":"AT+FINDSCANDATA=FF5B07=2"}"#;
-        let v = parse_bleuio_result(json).unwrap();
-        let result_type = get_bleuio_result_type(&v);
-        assert_eq!(result_type, BleuIOResponseType::CommandResponse);
-        assert_eq!(v["cmd"], "AT+FINDSCANDATA=FF5B07=2");
+        let v = parse_bleuio_response(json).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::CommandResponse);
+        assert_eq!(v.get_index(), Some(38));
     }
 
     #[test]
     fn test_get_bleuio_result_cmd() {
         let json = r#"{"C":38,"cmd":"AT+FINDSCANDATA=FF5B07=2"}"#;
-        let v = parse_bleuio_result(json).unwrap();
-        assert_eq!(v["cmd"], "AT+FINDSCANDATA=FF5B07=2");
+        let v = parse_bleuio_response(json).unwrap();
+        assert_eq!(v, BleuIOResponse::Command { idx: 38, cmd: "AT+FINDSCANDATA=FF5B07=2".to_string() });
     }
 
     #[test]
     fn test_get_bleuio_result_ack() {
         let json = r#"{"A":38,"err":0,"errMsg":"ok"}"#;
-        let v = parse_bleuio_result(json).unwrap();
-        let result_type = get_bleuio_result_type(&v);
-        assert_eq!(result_type, BleuIOResponseType::AcknowledgementResponse);
-        assert_eq!(v["err"], 0);
-        assert_eq!(v["errMsg"], "ok");
+        let v = parse_bleuio_response(json).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::AcknowledgementResponse);
+        assert_eq!(v, BleuIOResponse::Acknowledgement { idx: 38, err: BleuIOErrorCode::Success, err_msg: "ok".to_string() });
+    }
+
+    #[test]
+    fn test_get_bleuio_result_ack_error() {
+        let v = parse_bleuio_response(r#"{"A":7,"err":9,"errMsg":"busy"}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::Acknowledgement { idx: 7, err: BleuIOErrorCode::Busy, .. }));
+
+        let v = parse_bleuio_response(r#"{"A":7,"err":"0x0A","errMsg":"timeout"}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::Acknowledgement { err: BleuIOErrorCode::RequestTimedOut, .. }));
+
+        let v = parse_bleuio_response(r#"{"A":7,"err":255,"errMsg":"?"}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::Acknowledgement { err: BleuIOErrorCode::Unknown(255), .. }));
+        assert_eq!(BleuIOErrorCode::Unknown(255).to_string(), "Unknown error code 255 (0xFF)");
+        assert_eq!(BleuIOErrorCode::Busy.to_string(), "Busy (0x09)");
+
+        let v = parse_bleuio_response(r#"{"A":7,"err":"zz","errMsg":"?"}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::Acknowledgement { err: BleuIOErrorCode::Unknown(-1), .. }));
+    }
+
+    #[test]
+    fn test_get_bleuio_result_reply() {
+        let v = parse_bleuio_response(r#"{"R":38,"action":"scanning"}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::ReplyResponse);
+        match v {
+            BleuIOResponse::Reply { idx, data } => {
+                assert_eq!(idx, 38);
+                assert_eq!(data["action"], "scanning");
+                assert!(!data.contains_key("R"));
+            }
+            _ => panic!("Expected reply response"),
+        }
+    }
+
+    #[test]
+    fn test_get_bleuio_result_end() {
+        let v = parse_bleuio_response(r#"{"E":38,"nol":4}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::EndResponse);
+        assert_eq!(v, BleuIOResponse::End { idx: 38, nol: 4 });
+    }
+
+    #[test]
+    fn test_get_bleuio_result_scandata() {
+        let v = parse_bleuio_response(r#"{"S":3,"rssi":-56,"addr":"[1]C3:4B:4F:8E:21:0A","name":"HibouAir"}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::ScanDataResponse);
+        assert_eq!(v, BleuIOResponse::ScanData {
+            idx: 3,
+            rssi: Some(-56),
+            addr: "[1]C3:4B:4F:8E:21:0A".to_string(),
+            name: Some("HibouAir".to_string()),
+        });
+
+        let v = parse_bleuio_response(r#"{"S":3,"rssi":-80,"addr":"[0]00:11:22:33:44:55"}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::ScanData { name: None, .. }));
     }
 
     #[test]
     fn test_get_bleuio_result_scanfinddata() {
         let json = r#"{"SF":38,"addr":"F5:50:35:CF:B1:ED","type":0,"data":"0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02"}"#;
-        let v = parse_bleuio_result(json).unwrap();
-        let result_type = get_bleuio_result_type(&v);
-        assert_eq!(result_type, BleuIOResponseType::ScanFindDataResponse);
-        assert_eq!(v["addr"], "F5:50:35:CF:B1:ED");
-        assert_eq!(v["data"], "0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02");
+        let v = parse_bleuio_response(json).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::ScanFindDataResponse);
+        assert_eq!(v, BleuIOResponse::ScanFindData {
+            idx: 38,
+            rssi: None,
+            addr: "F5:50:35:CF:B1:ED".to_string(),
+            adv_type: 0,
+            data: "0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02".to_string(),
+        });
+
+        let json = r#"{"SF":38,"rssi":-71,"addr":"D2:B1:28:3F:42:D4","type":0,"data":"0201061BFF5B070504220049880B7F27EE00AB000A01000000000000024503"}"#;
+        let v = parse_bleuio_response(json).unwrap();
+        assert!(matches!(v, BleuIOResponse::ScanFindData { rssi: Some(-71), .. }));
+    }
+
+    #[test]
+    fn test_get_bleuio_result_scantarget() {
+        let json = r#"{"ST":12,"rssi":-62,"addr":"D2:B1:28:3F:42:D4","type":4,"data":"0201061BFF5B07"}"#;
+        let v = parse_bleuio_response(json).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::ScanTargetResponse);
+        assert!(matches!(v, BleuIOResponse::ScanTarget { idx: 12, rssi: Some(-62), adv_type: 4, .. }));
+    }
+
+    #[test]
+    fn test_get_bleuio_result_scanended() {
+        let v = parse_bleuio_response(r#"{"SE":38,"action":"scan completed"}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::ScanEndedResponse);
+        assert_eq!(v, BleuIOResponse::ScanEnded { idx: 38, action: "scan completed".to_string() });
+    }
+
+    #[test]
+    fn test_get_bleuio_result_event() {
+        let v = parse_bleuio_response(r#"{"779":"0x0000","evt":{"action":"connected","addr":"[1]D2:B1:28:3F:42:D4"}}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::EventResponse);
+        assert_eq!(v.get_index(), None);
+        match v {
+            BleuIOResponse::Event(evt) => {
+                assert_eq!(evt.code, 779);
+                assert_eq!(evt.conn_idx, Some(0));
+                assert_eq!(evt.data["evt"]["action"], "connected");
+            }
+            _ => panic!("Expected event response"),
+        }
+
+        let v = parse_bleuio_response(r#"{"772":"0xFFFF","evt":{"action":"disconnected"}}"#).unwrap();
+        assert!(matches!(v, BleuIOResponse::Event(BleuIOEvent { code: 772, conn_idx: None, .. })));
+    }
+
+    #[test]
+    fn test_connection_events() {
+        let event = |line: &str| match parse_bleuio_response(line).unwrap() {
+            BleuIOResponse::Event(evt) => evt.get_connection_event(),
+            other => panic!("Expected event response, got {other:?}"),
+        };
+        assert_eq!(
+            event(r#"{"779":"0x0000","evt":{"action":"connected","addr":"[1]D2:B1:28:3F:42:D4"}}"#),
+            Some(ConnectionEvent::Connected { conn_idx: Some(0), addr: Some("[1]D2:B1:28:3F:42:D4".to_string()) })
+        );
+        assert_eq!(
+            event(r#"{"772":"0x0000","evt":{"action":"disconnected","reason":19}}"#),
+            Some(ConnectionEvent::Disconnected { conn_idx: Some(0), reason: Some("19".to_string()) })
+        );
+        assert_eq!(event(r#"{"772":"0x0000","evt":{"action":"disconnected"}}"#).unwrap(), ConnectionEvent::Disconnected { conn_idx: Some(0), reason: None });
+        assert_eq!(event(r#"{"781":"0x0000","evt":{"action":"mtu changed","mtu":247}}"#), None);
+    }
+
+    #[test]
+    fn test_get_bleuio_result_unknown() {
+        let v = parse_bleuio_response(r#"{"X":1,"foo":"bar"}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::UnknownResponse);
+        assert_eq!(v.get_index(), None);
+
+        // Known key but missing required fields.
+        let v = parse_bleuio_response(r#"{"SF":38,"addr":"F5:50:35:CF:B1:ED"}"#).unwrap();
+        assert_eq!(v.get_type(), BleuIOResponseType::UnknownResponse);
+    }
+
+    #[test]
+    fn test_conformance_scan_session() {
+        // Complete AT+FINDSCANDATA session as sent by the dongle.
+        let lines = [
+            r#"{"C":38,"cmd":"AT+FINDSCANDATA=FF5B07=2"}"#,
+            r#"{"A":38,"err":0,"errMsg":"ok"}"#,
+            r#"{"R":38,"action":"scanning"}"#,
+            r#"{"E":38,"nol":4}"#,
+            r#"{"SF":38,"addr":"F5:50:35:CF:B1:ED","type":0,"data":"0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02"}"#,
+            r#"{"SF":38,"addr":"D2:B1:28:3F:42:D4","type":0,"data":"0201061BFF5B070504220049880B7F27EE00AB000A01000000000000024503"}"#,
+            r#"{"SE":38,"action":"scan completed"}"#,
+        ];
+        let expected = [
+            BleuIOResponseType::CommandResponse,
+            BleuIOResponseType::AcknowledgementResponse,
+            BleuIOResponseType::ReplyResponse,
+            BleuIOResponseType::EndResponse,
+            BleuIOResponseType::ScanFindDataResponse,
+            BleuIOResponseType::ScanFindDataResponse,
+            BleuIOResponseType::ScanEndedResponse,
+        ];
+        for (line, t) in lines.iter().zip(expected.iter()) {
+            let v = parse_bleuio_response(line).unwrap();
+            assert_eq!(&v.get_type(), t, "Checking type of {line}");
+            assert_eq!(v.get_index(), Some(38), "Checking index of {line}");
+        }
+    }
+
+    #[test]
+    fn test_error_code_mapping() {
+        assert_eq!(BleuIOErrorCode::try_from(0x09), Ok(BleuIOErrorCode::Busy));
+        assert_eq!(BleuIOErrorCode::try_from(0x12), Ok(BleuIOErrorCode::InsufficientBandwidth));
+        assert_eq!(BleuIOErrorCode::try_from(0x42), Err(UnknownErrorCode(0x42)));
+        assert_eq!(UnknownErrorCode(0x42).to_string(), "Unknown BleuIO error code 0x42");
+        assert_eq!(BleuIOErrorCode::RequestTimedOut.to_string(), "RequestTimedOut (0x0A)");
+
+        assert!(BleuIOError::Command(BleuIOErrorCode::Busy).is_retryable());
+        assert!(BleuIOError::Timeout.is_retryable());
+        assert!(!BleuIOError::Command(BleuIOErrorCode::InvalidParameter).is_retryable());
+        assert!(!BleuIOError::Closed.is_retryable());
+    }
+
+    #[test]
+    fn test_device_info_verbose() {
+        let lines: Vec<BleuIOResponse> = [
+            r#"{"C":2,"cmd":"ATI"}"#,
+            r#"{"A":2,"err":0,"errMsg":"ok"}"#,
+            r#"{"R":2,"dev":"Smart Sensor Devices","hw":"DA14683","fwVer":"2.7.5","gap role":"dual"}"#,
+            r#"{"R":2,"connected":false,"advertising":true}"#,
+            r#"{"E":2,"nol":4}"#,
+        ]
+        .iter()
+        .map(|l| parse_bleuio_line(l))
+        .collect();
+
+        let info = BleuIODeviceInfo::from_ati(&lines);
+        assert_eq!(info.firmware, "2.7.5");
+        assert_eq!(info.hardware, "DA14683");
+        assert_eq!(info.role, GapRole::Dual);
+        assert!(!info.connected);
+        assert!(info.advertising);
+        assert_eq!(info.get_firmware_version(), Some(FirmwareVersion(2, 7, 5)));
+        assert!(info.is_firmware_older_than(FirmwareVersion(2, 7, 6)));
+        assert!(!info.is_firmware_older_than(FirmwareVersion(2, 2, 0)));
+    }
+
+    #[test]
+    fn test_device_info_text() {
+        let lines: Vec<BleuIOResponse> = ["Smart Sensor Devices", "DA14683", "BleuIO Firmware Version: 2.1.3", "Central role", "Connected", "Not Advertising"]
+            .iter()
+            .map(|l| parse_bleuio_line(l))
+            .collect();
+
+        let info = BleuIODeviceInfo::from_ati(&lines);
+        assert_eq!(info.get_firmware_version(), Some(FirmwareVersion(2, 1, 3)));
+        assert_eq!(info.hardware, "DA14683");
+        assert_eq!(info.role, GapRole::Central);
+        assert!(info.connected);
+        assert!(!info.advertising);
+    }
+
+    #[test]
+    fn test_firmware_version() {
+        assert_eq!("2.7".parse::<FirmwareVersion>(), Ok(FirmwareVersion(2, 7, 0)));
+        assert_eq!("v2.10.1".parse::<FirmwareVersion>(), Ok(FirmwareVersion(2, 10, 1)));
+        assert!("2".parse::<FirmwareVersion>().is_err());
+        assert!("2.x.1".parse::<FirmwareVersion>().is_err());
+        assert!(FirmwareVersion(2, 10, 0) > FirmwareVersion(2, 9, 9));
+        assert!(!BleuIODeviceInfo::default().is_firmware_older_than(FirmwareVersion(9, 0, 0)));
+    }
+
+    #[test]
+    fn test_parse_mac() {
+        let verbose = [parse_bleuio_line(r#"{"R":3,"own_mac_addr":"40:48:fd:e5:2d:af"}"#)];
+        assert_eq!(parse_mac(&verbose), Some("40:48:FD:E5:2D:AF".to_string()));
+        let text = [parse_bleuio_line("40:48:FD:E5:2D:AF")];
+        assert_eq!(parse_mac(&text), Some("40:48:FD:E5:2D:AF".to_string()));
+        assert_eq!(parse_mac(&[parse_bleuio_line("ERROR")]), None);
+    }
+
+    #[test]
+    fn test_plain_scan_lines() {
+        let line = parse_bleuio_line("[F5:50:35:CF:B1:ED] Device Data [ADV]: 0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002");
+        assert_eq!(line, BleuIOResponse::ScanFindData {
+            idx: 0,
+            rssi: None,
+            addr: "F5:50:35:CF:B1:ED".to_string(),
+            adv_type: 0,
+            data: "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002".to_string(),
+        });
+        assert!(line.is_scan_result());
+
+        let line = parse_bleuio_line("[F5:50:35:CF:B1:ED] RSSI: -61, Device Data [SR]: 0201");
+        assert!(matches!(line, BleuIOResponse::ScanFindData { rssi: Some(-61), adv_type: 4, .. }));
+
+        let line = parse_bleuio_line("[01] Device: [1]D2:B1:28:3F:42:D4  RSSI: -74 (HibouAir)");
+        assert_eq!(line, BleuIOResponse::ScanData {
+            idx: 0,
+            rssi: Some(-74),
+            addr: "D2:B1:28:3F:42:D4".to_string(),
+            name: Some("HibouAir".to_string()),
+        });
+
+        assert_eq!(parse_bleuio_line("SCAN COMPLETE").get_type(), BleuIOResponseType::ScanEndedResponse);
+
+        // Anything else stays text.
+        for text in ["SCANNING...", "[F5:50:35:CF:B1:ED] Device Data [ADV]: nothex", "[zz] Device Data [ADV]: 02", "Device: nobody"] {
+            assert_eq!(parse_bleuio_line(text), BleuIOResponse::Text(text.to_string()));
+        }
     }
 }
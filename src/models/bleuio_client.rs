@@ -0,0 +1,682 @@
+use futures_channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures_util::{FutureExt, StreamExt};
+use serial2_tokio::SerialPort;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+
+use crate::models::autoexec::*;
+use crate::models::bleuio::*;
+use crate::models::capture::*;
+use crate::models::line_framer::*;
+use crate::models::transport::*;
+
+const BAUD_RATE: u32 = 115200;
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How `execute` deals with commands that fail.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,      // tries per command for Busy, RequestTimedOut and timeouts
+    pub retry_delay: Duration,  // wait between tries
+    pub reset_after: u32,       // reset the dongle (ATR) after this many failed commands in a row
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 3, retry_delay: Duration::from_millis(250), reset_after: 3 }
+    }
+}
+
+type Writer = Box<dyn AsyncWrite + Send + Unpin>;
+type SharedCapture = Arc<StdMutex<Option<CaptureWriter>>>;
+
+// Where the dongle traffic comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BleuIOSource {
+    Serial(String),                             // device path of a local dongle
+    Network { protocol: NetworkProtocol, addr: String },    // dongle behind ser2net or similar, addr is "host:port"
+    Replay { path: PathBuf, speed: f64 },       // capture file played back at `speed` (0 = no delays)
+}
+
+impl BleuIOSource {
+    // A port name is either a device path or a network address like "tcp://host:port".
+    pub fn from_port_name(port_name: &str) -> Self {
+        match parse_network_address(port_name) {
+            Some((protocol, addr)) => BleuIOSource::Network { protocol, addr },
+            None => BleuIOSource::Serial(port_name.to_string()),
+        }
+    }
+
+    // Return true if the dongle must be initialized and told to scan. A replay already
+    // contains that part of the conversation.
+    pub fn needs_handshake(&self) -> bool {
+        !matches!(self, BleuIOSource::Replay { .. })
+    }
+}
+
+// Which dongle a session talks to. Resolved to a source every time the session (re)connects,
+// so a dongle identified by serial number is found again under a new path after a replug.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DongleTarget {
+    Port(String),                               // fixed device path or network address
+    SerialNumber(String),                       // a specific dongle, by USB serial number
+    Replay { path: PathBuf, speed: f64 },       // capture file instead of a dongle
+}
+
+impl DongleTarget {
+    // Return the source to connect to, looking for dongles among the built-in USB ids and
+    // `extra`. A dongle that can't be found gives an empty port name.
+    pub fn resolve(&self, extra: &[BleuIOVariant]) -> BleuIOSource {
+        match self {
+            DongleTarget::Port(port_name) => BleuIOSource::from_port_name(port_name),
+            DongleTarget::SerialNumber(serial_number) => {
+                BleuIOSource::Serial(find_bleuio_by_serial(serial_number, extra).unwrap_or_default())
+            }
+            DongleTarget::Replay { path, speed } => BleuIOSource::Replay { path: path.clone(), speed: *speed },
+        }
+    }
+
+    // Return the name used to tell dongles apart in the UI.
+    pub fn get_name(&self) -> String {
+        match self {
+            DongleTarget::Port(port_name) => port_name.clone(),
+            DongleTarget::SerialNumber(serial_number) => serial_number.clone(),
+            DongleTarget::Replay { path, .. } => path.display().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for BleuIOSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BleuIOSource::Serial(port_name) => write!(f, "{port_name}"),
+            BleuIOSource::Network { protocol: NetworkProtocol::Raw, addr } => write!(f, "tcp://{addr}"),
+            BleuIOSource::Network { protocol: NetworkProtocol::Rfc2217, addr } => write!(f, "rfc2217://{addr}"),
+            BleuIOSource::Replay { path, .. } => write!(f, "replay of {}", path.display()),
+        }
+    }
+}
+
+// Async client for a BleuIO dongle, independent of the UI.
+//
+// A background task reads lines from the port. Replies to commands are handed to `send`,
+// which waits for the full C/A/R/E sequence of the command it wrote. Scan results
+// (S, SF, ST and SE lines) go to a separate stream, see `take_scan_results`, and so do
+// events such as connects and disconnects, see `take_events`.
+pub struct BleuIOClient {
+    writer: Mutex<Writer>,
+    responses: Mutex<UnboundedReceiver<BleuIOResponse>>,
+    scan_results: Option<UnboundedReceiver<BleuIOResponse>>,
+    events: Option<UnboundedReceiver<BleuIOEvent>>,
+    reader: JoinHandle<()>,
+    capture: SharedCapture,
+    response_timeout: Duration,
+    retry: RetryPolicy,
+    failures: AtomicU32,        // commands failed in a row, see `execute`
+    model: Option<BleuIOModel>, // known for dongles found by USB id
+    capabilities: Capabilities,
+}
+
+impl BleuIOClient {
+    // Open the serial port of a BleuIO dongle.
+    pub fn open(port_name: &str) -> Result<Self, BleuIOError> {
+        let port = SerialPort::open(port_name, BAUD_RATE)?;
+        port.set_dtr(true).ok();
+        port.set_rts(true).ok();
+        Ok(Self::from_stream(port))
+    }
+
+    // Connect to a dongle or start replaying a capture file. A serial dongle's model is looked
+    // up by its USB id, among the built-in ones and `extra`.
+    pub async fn connect(source: &BleuIOSource, extra: &[BleuIOVariant]) -> Result<Self, BleuIOError> {
+        match source {
+            BleuIOSource::Serial(port_name) => {
+                let mut client = Self::open(port_name)?;
+                if let Some(model) = find_bleuio_model(port_name, extra) {
+                    client.set_model(model);
+                }
+                Ok(client)
+            }
+            BleuIOSource::Network { protocol: NetworkProtocol::Raw, addr } => Ok(Self::from_stream(connect_tcp(addr).await?)),
+            BleuIOSource::Network { protocol: NetworkProtocol::Rfc2217, addr } => {
+                Ok(Self::from_stream(rfc2217(connect_tcp(addr).await?, BAUD_RATE)))
+            }
+            BleuIOSource::Replay { path, speed } => Ok(Self::from_stream(replay(read_capture(path)?, *speed))),
+        }
+    }
+
+    // Run the client on any byte stream talking the BleuIO protocol.
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        let (response_tx, response_rx) = unbounded();
+        let (scan_tx, scan_rx) = unbounded();
+        let (event_tx, event_rx) = unbounded();
+        let capture: SharedCapture = Arc::new(StdMutex::new(None));
+
+        BleuIOClient {
+            writer: Mutex::new(Box::new(writer)),
+            responses: Mutex::new(response_rx),
+            scan_results: Some(scan_rx),
+            events: Some(event_rx),
+            reader: tokio::spawn(read_lines(reader, Channels { responses: response_tx, scan_results: scan_tx, events: event_tx }, capture.clone())),
+            capture,
+            response_timeout: RESPONSE_TIMEOUT,
+            retry: RetryPolicy::default(),
+            failures: AtomicU32::new(0),
+            model: None,
+            capabilities: Capabilities::default(),
+        }
+    }
+
+    // Write every line received from now on to a capture file.
+    pub fn set_capture(&self, writer: CaptureWriter) {
+        if let Ok(mut capture) = self.capture.lock() {
+            *capture = Some(writer);
+        }
+    }
+
+    // Set how long `send` waits for the end of a response.
+    pub fn set_response_timeout(&mut self, response_timeout: Duration) {
+        self.response_timeout = response_timeout;
+    }
+
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    // Set which model the dongle is, and with it what the client may ask of it. Without a model
+    // (network, replay) the dongle is assumed to be a standard BleuIO.
+    pub fn set_model(&mut self, model: BleuIOModel) {
+        self.model = Some(model);
+        self.capabilities = model.get_capabilities();
+    }
+
+    pub fn get_model(&self) -> Option<BleuIOModel> {
+        self.model
+    }
+
+    pub fn get_capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    // Take the stream of scan results. Can only be taken once; the stream ends when the port closes.
+    pub fn take_scan_results(&mut self) -> Option<UnboundedReceiver<BleuIOResponse>> {
+        self.scan_results.take()
+    }
+
+    // Take the stream of events (connected, disconnected, ...). Can only be taken once.
+    pub fn take_events(&mut self) -> Option<UnboundedReceiver<BleuIOEvent>> {
+        self.events.take()
+    }
+
+    // Send a command and collect every response line that carries its command index,
+    // up to and including the "E" line. An error code in the "A" line is returned as Err.
+    pub async fn send(&self, cmd: BleuIOCommand) -> Result<Vec<BleuIOResponse>, BleuIOError> {
+        let mut writer = self.writer.lock().await;
+        let mut responses = self.responses.lock().await;
+
+        // Throw away anything left over from an earlier command that timed out.
+        while let Some(Some(_)) = responses.next().now_or_never() {}
+
+        let wire = cmd.to_wire();
+        writer.write_all(wire.as_bytes()).await?;
+        writer.flush().await?;
+        if !cmd.expects_response() {
+            return Ok(Vec::new());
+        }
+
+        timeout(self.response_timeout, collect_response(&mut responses, wire.trim_end()))
+            .await
+            .map_err(|_| BleuIOError::Timeout)?
+    }
+
+    // Send a command like `send`, but try again while the dongle is busy or doesn't answer.
+    // When commands keep failing that way the dongle is reset with ATR and Err(Reset) is
+    // returned; it then drops off the bus and has to be reconnected and initialized again.
+    // Other errors, such as a bad parameter, are the command's fault and count for nothing.
+    pub async fn execute(&self, cmd: BleuIOCommand) -> Result<Vec<BleuIOResponse>, BleuIOError> {
+        let mut attempt = 1;
+        let result = loop {
+            match self.send(cmd.clone()).await {
+                Err(e) if e.is_retryable() && attempt < self.retry.max_attempts => {
+                    attempt += 1;
+                    sleep(self.retry.retry_delay).await;
+                }
+                result => break result,
+            }
+        };
+
+        match &result {
+            Ok(_) => self.failures.store(0, Ordering::Relaxed),
+            Err(e) if e.is_retryable() => {
+                let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+                if failures >= self.retry.reset_after {
+                    self.failures.store(0, Ordering::Relaxed);
+                    self.send(BleuIOCommand::Atr).await.ok();
+                    return Err(BleuIOError::Reset(failures));
+                }
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    // Find out how the dongle is set up by sending AT: an echoed "AT" means echo is on, and the
+    // answer is either a JSON C/A/E sequence (verbose) or a plain "OK". Dongles are shared with
+    // other tools, so any combination is possible, and one may have left a scan running (as does
+    // an autoexec scan at power up); a Ctrl-C first stops it so it doesn't swallow the AT.
+    pub async fn detect_mode(&self) -> Result<DongleMode, BleuIOError> {
+        let mut writer = self.writer.lock().await;
+        let mut responses = self.responses.lock().await;
+        while let Some(Some(_)) = responses.next().now_or_never() {}
+
+        writer.write_all(BleuIOCommand::StopScan.to_wire().as_bytes()).await?;
+        writer.write_all(BleuIOCommand::At.to_wire().as_bytes()).await?;
+        writer.flush().await?;
+
+        let detect = async {
+            let mut echo = false;
+            while let Some(response) = responses.next().await {
+                match response {
+                    BleuIOResponse::Text(text) if text == "AT" => echo = true,
+                    BleuIOResponse::Text(text) if text == "OK" => return Ok(DongleMode { echo, verbose: false }),
+                    BleuIOResponse::End { .. } => return Ok(DongleMode { echo, verbose: true }),
+                    _ => {}
+                }
+            }
+            Err(BleuIOError::Closed)
+        };
+        timeout(self.response_timeout, detect).await.map_err(|_| BleuIOError::Timeout)?
+    }
+
+    // Turn echo off, verbose (JSON) output on, and have scan results include RSSI.
+    // Only what isn't already set up is changed.
+    pub async fn init(&self) -> Result<(), BleuIOError> {
+        let mode = match self.detect_mode().await {
+            Ok(mode) => mode,
+            // Unsure, switch both.
+            Err(_) => DongleMode { echo: true, verbose: false },
+        };
+        if mode.echo {
+            self.execute(BleuIOCommand::Ate0).await?;
+        }
+        if !mode.verbose {
+            self.execute(BleuIOCommand::Atv1).await?;
+        }
+        if self.capabilities.show_rssi {
+            self.execute(BleuIOCommand::AtShowRssi(true)).await?;
+        }
+        Ok(())
+    }
+
+    // Ask the dongle for firmware, hardware, role and its own MAC address.
+    pub async fn query_info(&self) -> Result<BleuIODeviceInfo, BleuIOError> {
+        let mut info = BleuIODeviceInfo::from_ati(&self.execute(BleuIOCommand::AtI).await?);
+        info.mac = parse_mac(&self.execute(BleuIOCommand::AtGetMac).await?);
+        info.model = self.model;
+        Ok(info)
+    }
+
+    // Return the commands the dongle runs by itself at power up.
+    pub async fn read_autoexec(&self) -> Result<Vec<String>, BleuIOError> {
+        require(self.capabilities.autoexec)?;
+        Ok(parse_autoexec_list(&self.execute(BleuIOCommand::AtAutoExecList).await?))
+    }
+
+    // Replace the autoexec list with `commands` and return the list as the dongle now has it.
+    pub async fn write_autoexec(&self, commands: &[String]) -> Result<Vec<String>, BleuIOError> {
+        require(self.capabilities.autoexec)?;
+        self.execute(BleuIOCommand::AtClrAutoExec).await?;
+        for cmd in commands {
+            self.execute(BleuIOCommand::AtAutoExecAdd(cmd.clone())).await?;
+        }
+        self.read_autoexec().await
+    }
+
+    // Start a scan (AT+FINDSCANDATA, AT+SCANTARGET, ...). Results arrive on the scan stream.
+    pub async fn start_scan(&self, scan: BleuIOCommand) -> Result<(), BleuIOError> {
+        self.execute(scan).await?;
+        Ok(())
+    }
+}
+
+impl BleuIOClient {
+    // Flush the capture file and release the port. The port is closed once this returns, so
+    // another program can open it right away.
+    pub async fn close(mut self) {
+        if let Ok(capture) = self.capture.lock() {
+            if let Some(writer) = capture.as_ref() {
+                writer.flush().ok();
+            }
+        }
+        self.writer.lock().await.shutdown().await.ok();
+        self.reader.abort();
+        (&mut self.reader).await.ok();
+    }
+}
+
+impl Drop for BleuIOClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+// Fail the way the dongle itself would for a command the model doesn't have.
+fn require(supported: bool) -> Result<(), BleuIOError> {
+    if supported {
+        Ok(())
+    } else {
+        Err(BleuIOError::Command(BleuIOErrorCode::NotSupported))
+    }
+}
+
+// Where `read_lines` delivers what it reads.
+struct Channels {
+    responses: UnboundedSender<BleuIOResponse>,
+    scan_results: UnboundedSender<BleuIOResponse>,
+    events: UnboundedSender<BleuIOEvent>,
+}
+
+// Read lines until the port closes and route them to the response, scan or event channel.
+// Corrupt bytes are dropped by the framer and logged; they never end the session.
+async fn read_lines<R>(mut reader: R, channels: Channels, capture: SharedCapture)
+where
+    R: AsyncRead + Unpin,
+{
+    let mut framer = LineFramer::new();
+    let mut buf = [0u8; 1024];
+    let mut lines = Vec::new();
+
+    loop {
+        let n = match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => n,
+        };
+        let discarded = framer.get_discarded();
+        framer.feed(&buf[..n], &mut lines);
+        if framer.get_discarded() > discarded {
+            eprintln!(
+                "Discarded {} corrupt bytes from the dongle ({} in total)",
+                framer.get_discarded() - discarded,
+                framer.get_discarded()
+            );
+        }
+        for line in lines.drain(..) {
+            route_line(&line, &channels, &capture);
+        }
+    }
+    if let Some(line) = framer.finish() {
+        route_line(&line, &channels, &capture);
+    }
+}
+
+fn route_line(line: &str, channels: &Channels, capture: &SharedCapture) {
+    if let Ok(mut capture) = capture.lock() {
+        if let Some(writer) = capture.as_mut() {
+            writer.write_line(line).ok();
+        }
+    }
+    match parse_bleuio_line(line) {
+        BleuIOResponse::Event(event) => {
+            channels.events.unbounded_send(event).ok();
+        }
+        response if response.is_scan_result() => {
+            channels.scan_results.unbounded_send(response).ok();
+        }
+        response => {
+            channels.responses.unbounded_send(response).ok();
+        }
+    }
+}
+
+// Wait for the response to the command just written. The command index is taken from
+// the "C" line; lines belonging to other commands are skipped.
+async fn collect_response(
+    responses: &mut UnboundedReceiver<BleuIOResponse>,
+    echo: &str,
+) -> Result<Vec<BleuIOResponse>, BleuIOError> {
+    let mut idx: Option<u32> = None;
+    let mut last_error = BleuIOErrorCode::Success;
+    let mut lines = Vec::new();
+
+    while let Some(response) = responses.next().await {
+        if let BleuIOResponse::Text(text) = &response {
+            // Non-verbose replies ("ECHO OFF", "VERBOSE ON", ...) have no index and no end line.
+            if idx.is_none() && text != echo {
+                return Ok(vec![response]);
+            }
+            continue;
+        }
+
+        if idx.is_none() {
+            match response {
+                BleuIOResponse::Command { idx: i, .. } => idx = Some(i),
+                _ => continue,
+            }
+        }
+        if response.get_index() != idx {
+            continue;
+        }
+
+        match response {
+            BleuIOResponse::Acknowledgement { err, .. } => last_error = err,
+            BleuIOResponse::End { .. } => {
+                lines.push(response);
+                return match last_error {
+                    BleuIOErrorCode::Success => Ok(lines),
+                    err => Err(BleuIOError::Command(err)),
+                };
+            }
+            _ => {}
+        }
+        lines.push(response);
+    }
+
+    Err(BleuIOError::Closed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{duplex, DuplexStream};
+
+    // Read one command line written by the client.
+    async fn read_command(dongle: &mut DuplexStream) -> String {
+        let mut buf = Vec::new();
+        while !buf.ends_with(b"\r\n") {
+            let mut byte = [0u8; 1];
+            dongle.read_exact(&mut byte).await.unwrap();
+            buf.push(byte[0]);
+        }
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_send_collects_response() {
+        let (host, mut dongle) = duplex(4096);
+        let client = BleuIOClient::from_stream(host);
+
+        let reply = tokio::spawn(async move {
+            assert_eq!(read_command(&mut dongle).await, "ATI\r\n");
+            dongle.write_all(concat!(
+                "{\"E\":6,\"nol\":3}\r\n",                           // stale, from an older command
+                "{\"C\":7,\"cmd\":\"ATI\"}\r\n",
+                "{\"A\":7,\"err\":0,\"errMsg\":\"ok\"}\r\n",
+                "{\"R\":7,\"fwVer\":\"2.7.5\"}\r\n",
+                "{\"E\":7,\"nol\":3}\r\n",
+            ).as_bytes()).await.unwrap();
+            dongle
+        });
+
+        let lines = client.send(BleuIOCommand::AtI).await.unwrap();
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|l| l.get_index() == Some(7)));
+        assert_eq!(lines[3], BleuIOResponse::End { idx: 7, nol: 3 });
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_reports_error_code() {
+        let (host, mut dongle) = duplex(4096);
+        let client = BleuIOClient::from_stream(host);
+
+        let reply = tokio::spawn(async move {
+            read_command(&mut dongle).await;
+            dongle.write_all(concat!(
+                "{\"C\":1,\"cmd\":\"AT+CENTRAL\"}\r\n",
+                "{\"A\":1,\"err\":9,\"errMsg\":\"busy\"}\r\n",
+                "{\"E\":1,\"nol\":2}\r\n",
+            ).as_bytes()).await.unwrap();
+            dongle
+        });
+
+        let result = client.send(BleuIOCommand::AtCentral).await;
+        assert!(matches!(result, Err(BleuIOError::Command(BleuIOErrorCode::Busy))));
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_non_verbose_reply() {
+        let (host, mut dongle) = duplex(4096);
+        let client = BleuIOClient::from_stream(host);
+
+        let reply = tokio::spawn(async move {
+            read_command(&mut dongle).await;
+            // Echo is still on, so the dongle repeats the command first.
+            dongle.write_all(b"ATE0\r\nECHO OFF\r\n").await.unwrap();
+            dongle
+        });
+
+        let lines = client.send(BleuIOCommand::Ate0).await.unwrap();
+        assert_eq!(lines, vec![BleuIOResponse::Text("ECHO OFF".to_string())]);
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_timeout_and_closed() {
+        let (host, dongle) = duplex(4096);
+        let mut client = BleuIOClient::from_stream(host);
+        client.set_response_timeout(Duration::from_millis(50));
+
+        assert!(matches!(client.send(BleuIOCommand::At).await, Err(BleuIOError::Timeout)));
+
+        drop(dongle);
+        assert!(client.send(BleuIOCommand::At).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_execute_retries_when_busy() {
+        let (host, mut dongle) = duplex(4096);
+        let mut client = BleuIOClient::from_stream(host);
+        client.set_retry_policy(RetryPolicy { max_attempts: 3, retry_delay: Duration::from_millis(1), reset_after: 3 });
+
+        let reply = tokio::spawn(async move {
+            for (idx, err) in [(1, 9), (2, 0)] {
+                assert_eq!(read_command(&mut dongle).await, "AT+CENTRAL\r\n");
+                dongle.write_all(format!(
+                    "{{\"C\":{idx},\"cmd\":\"AT+CENTRAL\"}}\r\n{{\"A\":{idx},\"err\":{err}}}\r\n{{\"E\":{idx},\"nol\":2}}\r\n"
+                ).as_bytes()).await.unwrap();
+            }
+            dongle
+        });
+
+        let lines = client.execute(BleuIOCommand::AtCentral).await.unwrap();
+        assert_eq!(lines.last(), Some(&BleuIOResponse::End { idx: 2, nol: 2 }));
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute_resets_after_repeated_failures() {
+        let (host, mut dongle) = duplex(4096);
+        let mut client = BleuIOClient::from_stream(host);
+        client.set_retry_policy(RetryPolicy { max_attempts: 1, retry_delay: Duration::ZERO, reset_after: 2 });
+
+        // Bad parameters (0x04) are the command's fault and never lead to a reset; a busy
+        // dongle (0x09) twice in a row does.
+        let reply = tokio::spawn(async move {
+            for (idx, err) in [(1, 4), (2, 4), (3, 4), (4, 9), (5, 9)] {
+                read_command(&mut dongle).await;
+                dongle.write_all(format!(
+                    "{{\"C\":{idx},\"cmd\":\"AT+GAPSCAN\"}}\r\n{{\"A\":{idx},\"err\":{err}}}\r\n{{\"E\":{idx},\"nol\":2}}\r\n"
+                ).as_bytes()).await.unwrap();
+            }
+            assert_eq!(read_command(&mut dongle).await, "ATR\r\n");
+            dongle
+        });
+
+        for _ in 0..3 {
+            let bad = client.execute(BleuIOCommand::AtGapScan(None)).await;
+            assert!(matches!(bad, Err(BleuIOError::Command(BleuIOErrorCode::InvalidParameter))));
+        }
+        let first = client.execute(BleuIOCommand::AtGapScan(None)).await;
+        assert!(matches!(first, Err(BleuIOError::Command(BleuIOErrorCode::Busy))));
+        let second = client.execute(BleuIOCommand::AtGapScan(None)).await;
+        assert!(matches!(second, Err(BleuIOError::Reset(2))));
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_detect_mode() {
+        let (host, mut dongle) = duplex(4096);
+        let client = BleuIOClient::from_stream(host);
+
+        let reply = tokio::spawn(async move {
+            // Echo on, plain text, with a leftover scan line from another tool in between.
+            assert_eq!(read_command(&mut dongle).await, "\x03AT\r\n");
+            dongle.write_all(b"AT\r\n[F5:50:35:CF:B1:ED] Device Data [ADV]: 0201\r\nOK\r\n").await.unwrap();
+            // Echo off, JSON.
+            read_command(&mut dongle).await;
+            dongle.write_all(b"{\"C\":4,\"cmd\":\"AT\"}\r\n{\"A\":4,\"err\":0}\r\n{\"E\":4,\"nol\":2}\r\n").await.unwrap();
+            dongle
+        });
+
+        assert_eq!(client.detect_mode().await.unwrap(), DongleMode { echo: true, verbose: false });
+        assert_eq!(client.detect_mode().await.unwrap(), DongleMode { echo: false, verbose: true });
+        reply.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scan_results_stream() {
+        let (host, mut dongle) = duplex(4096);
+        let mut client = BleuIOClient::from_stream(host);
+        let mut scan_results = client.take_scan_results().unwrap();
+        assert!(client.take_scan_results().is_none());
+
+        dongle.write_all(concat!(
+            "{\"SF\":38,\"addr\":\"F5:50:35:CF:B1:ED\",\"type\":0,\"data\":\"0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02\"}\r\n",
+            "{\"SE\":38,\"action\":\"scan completed\"}\r\n",
+        ).as_bytes()).await.unwrap();
+        drop(dongle);
+
+        let first = scan_results.next().await.unwrap();
+        assert!(matches!(first, BleuIOResponse::ScanFindData { idx: 38, .. }));
+        let second = scan_results.next().await.unwrap();
+        assert_eq!(second.get_type(), BleuIOResponseType::ScanEndedResponse);
+        assert!(scan_results.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_bytes_do_not_end_session() {
+        let (host, mut dongle) = duplex(4096);
+        let mut client = BleuIOClient::from_stream(host);
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        dongle.write_all(b"\xff\x00junk\xc3\r\n").await.unwrap();
+        dongle.write_all(
+            b"{\"SF\":38,\"addr\":\"F5:50:35:CF:B1:ED\",\"type\":0,\"data\":\"0201061BFF5B07050422013FBD007D27E000BB00F419000000000000020A02\"}\r\n",
+        ).await.unwrap();
+        drop(dongle);
+
+        let first = scan_results.next().await.unwrap();
+        assert!(matches!(first, BleuIOResponse::ScanFindData { idx: 38, .. }));
+        assert!(scan_results.next().await.is_none());
+    }
+}
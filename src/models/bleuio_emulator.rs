@@ -0,0 +1,567 @@
+// Test support: a BleuIO dongle emulated on a Linux pseudo-terminal.
+//
+// The emulator owns the master side of a pty and answers the commands written to the
+// slave side the same way the dongle firmware does, so the real serial code can be
+// exercised without hardware. Open `slave_path()` like any other serial port.
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+// The emulated dongle's own address, as answered to AT+GETMAC.
+pub const EMULATOR_MAC: &str = "40:48:FD:E5:2D:AF";
+
+// What every emulated sensor exposes over GATT: service, characteristic, value handle,
+// properties and value.
+const GATT_TABLE: [(&str, &str, u16, &str, &[u8]); 6] = [
+    ("1800", "2A00", 0x0003, "READ", b"HibouAir"),
+    ("180A", "2A29", 0x0011, "READ", b"Smart Sensor Devices"),
+    ("180A", "2A24", 0x0013, "READ", b"HibouAir CO2"),
+    ("180A", "2A26", 0x0015, "READ", b"1.4.2"),
+    ("180F", "2A19", 0x001B, "READ|NOTIFY", &[87]),
+    ("FFF0", "FFF1", 0x0030, "WRITE", &[]),
+];
+
+// A scripted advertisement streamed while the emulator is scanning.
+#[derive(Debug, Clone)]
+pub struct EmulatedAdvert {
+    pub addr: String,
+    pub rssi: i32,
+    pub data: String,
+}
+
+impl EmulatedAdvert {
+    pub fn new(addr: &str, rssi: i32, data: &str) -> Self {
+        EmulatedAdvert { addr: addr.to_string(), rssi, data: data.to_string() }
+    }
+}
+
+// Dongle state the emulator keeps between commands. Defaults match a freshly plugged dongle.
+struct DongleState {
+    echo: bool,
+    verbose: bool,
+    show_rssi: bool,
+    idx: u32,
+    adverts: Vec<EmulatedAdvert>,
+    scanning: Arc<AtomicBool>,      // an open-ended scan is running
+    connected: Option<String>,      // address of the sensor we're connected to
+    autoexec: Vec<String>,          // commands to run at power up
+}
+
+pub struct BleuIOEmulator {
+    slave_path: String,
+    _slave: File,
+    stop: Arc<AtomicBool>,
+    scanning: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BleuIOEmulator {
+    // Start an emulator in factory state (echo on, non-verbose).
+    pub fn start(adverts: Vec<EmulatedAdvert>) -> std::io::Result<Self> {
+        Self::start_with_state(adverts, true, false)
+    }
+
+    // Start an emulator with a given echo/verbose state, as if another tool had configured it.
+    pub fn start_with_state(adverts: Vec<EmulatedAdvert>, echo: bool, verbose: bool) -> std::io::Result<Self> {
+        let (master, slave_path) = open_pty()?;
+        // Keep the slave open ourselves so the master doesn't see EOF between client sessions.
+        let slave = std::fs::OpenOptions::new().read(true).write(true).open(&slave_path)?;
+        make_raw(&slave)?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let scanning = Arc::new(AtomicBool::new(false));
+        let state = DongleState { echo, verbose, show_rssi: false, idx: 0, adverts, scanning: scanning.clone(), connected: None, autoexec: Vec::new() };
+        let thread_stop = stop.clone();
+        let thread = std::thread::spawn(move || run(master, state, thread_stop));
+
+        Ok(BleuIOEmulator { slave_path, _slave: slave, stop, scanning, thread: Some(thread) })
+    }
+
+    // Path of the pty device to open as the dongle's serial port.
+    pub fn slave_path(&self) -> &str {
+        &self.slave_path
+    }
+
+    // Return true while a scan without duration is running, i.e. until Ctrl-C.
+    pub fn is_scanning(&self) -> bool {
+        self.scanning.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for BleuIOEmulator {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+fn open_pty() -> std::io::Result<(File, String)> {
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let master = File::from_raw_fd(fd);
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut name = [0 as libc::c_char; 128];
+        if libc::ptsname_r(fd, name.as_mut_ptr(), name.len()) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let path = CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned();
+        Ok((master, path))
+    }
+}
+
+fn make_raw(file: &File) -> std::io::Result<()> {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(file.as_raw_fd(), &mut termios) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        if libc::tcsetattr(file.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// Wait up to `timeout` for the master to become readable.
+fn wait_readable(master: &File, timeout: Duration) -> bool {
+    let mut pfd = libc::pollfd { fd: master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+    let n = unsafe { libc::poll(&mut pfd, 1, timeout.as_millis() as libc::c_int) };
+    n > 0 && (pfd.revents & libc::POLLIN) != 0
+}
+
+fn run(mut master: File, mut state: DongleState, stop: Arc<AtomicBool>) {
+    let mut input = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        if !wait_readable(&master, Duration::from_millis(20)) {
+            continue;
+        }
+        let mut buf = [0u8; 256];
+        let n = match master.read(&mut buf) {
+            Ok(0) | Err(_) => continue,
+            Ok(n) => n,
+        };
+
+        for &byte in &buf[..n] {
+            match byte {
+                // Ctrl-C stops an ongoing scan and is ignored otherwise.
+                0x03 => {
+                    if state.scanning.swap(false, Ordering::Relaxed) {
+                        let idx = state.idx;
+                        reply(&mut master, &state, &[format!(r#"{{"SE":{idx},"action":"scan completed"}}"#)], &["SCAN COMPLETE"]);
+                    }
+                    input.clear();
+                }
+                b'\r' | b'\n' => {
+                    if !input.is_empty() {
+                        let cmd = String::from_utf8_lossy(&input).into_owned();
+                        input.clear();
+                        handle_command(&mut master, &mut state, &cmd);
+                    }
+                }
+                _ => input.push(byte),
+            }
+        }
+    }
+}
+
+// Write response lines, JSON or plain text depending on the verbose setting.
+fn reply(master: &mut File, state: &DongleState, json: &[String], text: &[&str]) {
+    let mut out = String::new();
+    if state.verbose {
+        for line in json {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    } else {
+        for line in text {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    }
+    master.write_all(out.as_bytes()).ok();
+}
+
+// Build the C/A/R.../E sequence for a command.
+fn verbose_response(idx: u32, cmd: &str, err: i64, replies: &[String]) -> Vec<String> {
+    let err_msg = if err == 0 { "ok" } else { "error" };
+    let mut lines = vec![
+        format!(r#"{{"C":{idx},"cmd":"{cmd}"}}"#),
+        format!(r#"{{"A":{idx},"err":{err},"errMsg":"{err_msg}"}}"#),
+    ];
+    lines.extend(replies.iter().map(|r| format!(r#"{{"R":{idx},{r}}}"#)));
+    lines.push(format!(r#"{{"E":{idx},"nol":{}}}"#, lines.len() + 1));
+    lines
+}
+
+fn handle_command(master: &mut File, state: &mut DongleState, cmd: &str) {
+    if state.echo {
+        master.write_all(format!("{cmd}\r\n").as_bytes()).ok();
+    }
+    state.idx += 1;
+    let idx = state.idx;
+    let (name, args) = cmd.split_once('=').unwrap_or((cmd, ""));
+
+    match name {
+        "AT" => reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["OK"]),
+        "ATE0" => {
+            state.echo = false;
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["ECHO OFF"]);
+        }
+        "ATE1" => {
+            state.echo = true;
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["ECHO ON"]);
+        }
+        "ATV0" => {
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &[]);
+            state.verbose = false;
+            reply(master, state, &[], &["VERBOSE OFF"]);
+        }
+        "ATV1" => {
+            state.verbose = true;
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &[]);
+        }
+        "ATI" => reply(
+            master,
+            state,
+            &verbose_response(idx, cmd, 0, &[
+                r#""dev":"Smart Sensor Devices","hw":"DA14683","fwVer":"2.7.5","gap role":"dual""#.to_string(),
+                r#""connected":false,"advertising":false"#.to_string(),
+            ]),
+            &["Smart Sensor Devices", "DA14683", "BleuIO Firmware Version: 2.7.5", "Dual role", "Not Connected", "Not Advertising"],
+        ),
+        "AT+GETMAC" => reply(
+            master,
+            state,
+            &verbose_response(idx, cmd, 0, &[format!(r#""own_mac_addr":"{EMULATOR_MAC}""#)]),
+            &[EMULATOR_MAC],
+        ),
+        "AT+CENTRAL" => reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["Central role"]),
+        "AT+SHOWRSSI" => {
+            state.show_rssi = args == "1";
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["SHOW RSSI ON"]);
+        }
+        // A second scan is refused until the running one is stopped with Ctrl-C.
+        "AT+FINDSCANDATA" | "AT+SCANTARGET" if state.scanning.load(Ordering::Relaxed) => {
+            reply(master, state, &verbose_response(idx, cmd, 0x03, &[]), &["ERROR"]);
+        }
+        "AT+FINDSCANDATA" => {
+            let pattern = args.split('=').next().unwrap_or("");
+            start_scan(master, state, idx, cmd, args, "SF", |a| a.data.contains(pattern));
+        }
+        "AT+SCANTARGET" => {
+            let targets: Vec<&str> = args
+                .split('=')
+                .next()
+                .unwrap_or("")
+                .split(';')
+                .map(|t| t.trim_start_matches("[0]").trim_start_matches("[1]"))
+                .collect();
+            start_scan(master, state, idx, cmd, args, "ST", |a| targets.contains(&a.addr.as_str()));
+        }
+        // Only sensors we have adverts for accept a connection; for others no event ever comes.
+        "AT+GAPCONNECT" if state.scanning.load(Ordering::Relaxed) || state.connected.is_some() => {
+            reply(master, state, &verbose_response(idx, cmd, 0x09, &[]), &["ERROR"]);
+        }
+        "AT+GAPCONNECT" => {
+            reply(master, state, &verbose_response(idx, cmd, 0, &[r#""action":"connecting""#.to_string()]), &["Trying to connect..."]);
+            let mac = args.trim_start_matches("[0]").trim_start_matches("[1]");
+            if state.adverts.iter().any(|a| a.addr == mac) {
+                state.connected = Some(mac.to_string());
+                reply(
+                    master,
+                    state,
+                    &[format!(r#"{{"779":"0x0000","evt":{{"action":"connected","addr":"{args}"}}}}"#)],
+                    &["CONNECTED."],
+                );
+            }
+        }
+        "AT+GETSERVICES" if state.connected.is_some() => {
+            let replies: Vec<String> = GATT_TABLE
+                .iter()
+                .map(|(svc, uuid, handle, props, _)| format!(r#""svc":"{svc}","uuid":"{uuid}","handle":"0x{handle:04X}","props":"{props}""#))
+                .collect();
+            let text: Vec<String> = GATT_TABLE
+                .iter()
+                .map(|(svc, uuid, handle, props, _)| format!("Service {svc}: handle {handle:04X} {uuid} {props}"))
+                .collect();
+            let text: Vec<&str> = text.iter().map(String::as_str).collect();
+            reply(master, state, &verbose_response(idx, cmd, 0, &replies), &text);
+        }
+        "AT+GATTCREAD" if state.connected.is_some() => {
+            let handle = u16::from_str_radix(args, 16).ok();
+            match GATT_TABLE.iter().find(|c| Some(c.2) == handle && c.3.contains("READ")) {
+                Some((_, _, handle, _, value)) => {
+                    let hex = hex::encode_upper(value);
+                    let ascii = String::from_utf8_lossy(value).into_owned();
+                    reply(
+                        master,
+                        state,
+                        &verbose_response(idx, cmd, 0, &[format!(
+                            r#""hdl":"0x{handle:04X}","hex":"0x{hex}","size":{},"ascii":"{ascii}""#,
+                            value.len()
+                        )]),
+                        &[&format!("VALUE (handle: {handle:04X})"), &format!("Hex: 0x{hex}"), &format!("Size: {}", value.len())],
+                    );
+                }
+                None => reply(master, state, &verbose_response(idx, cmd, 0x07, &[]), &["ERROR"]),
+            }
+        }
+        "AT+GAPDISCONNECT" if state.connected.is_some() => {
+            state.connected = None;
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &[]);
+            reply(master, state, &[r#"{"772":"0x0000","evt":{"action":"disconnected","reason":22}}"#.to_string()], &["DISCONNECTED."]);
+        }
+        "AT+AUTOEXEC" if args.is_empty() => {
+            let replies: Vec<String> = state.autoexec.iter().map(|c| format!(r#""autoexec":"{c}""#)).collect();
+            let text: Vec<&str> = state.autoexec.iter().map(String::as_str).collect();
+            reply(master, state, &verbose_response(idx, cmd, 0, &replies), &text);
+        }
+        // Refuses anything but AT commands, so tests can make a write fail half way.
+        "AT+AUTOEXEC" if !args.starts_with("AT") => {
+            reply(master, state, &verbose_response(idx, cmd, 0x07, &[]), &["ERROR"]);
+        }
+        "AT+AUTOEXEC" => {
+            state.autoexec.push(args.to_string());
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["OK"]);
+        }
+        "AT+CLRAUTOEXEC" => {
+            state.autoexec.clear();
+            reply(master, state, &verbose_response(idx, cmd, 0, &[]), &["AUTOEXEC CLEARED"]);
+        }
+        _ => reply(master, state, &verbose_response(idx, cmd, 0x07, &[]), &["ERROR"]),
+    }
+}
+
+// Answer a scan command and stream every advert `matches` accepts as `kind` ("SF" or "ST") lines.
+fn start_scan<F: Fn(&EmulatedAdvert) -> bool>(
+    master: &mut File,
+    state: &mut DongleState,
+    idx: u32,
+    cmd: &str,
+    args: &str,
+    kind: &str,
+    matches: F,
+) {
+    state.scanning.store(args.split('=').nth(1).is_none(), Ordering::Relaxed);
+    reply(
+        master,
+        state,
+        &verbose_response(idx, cmd, 0, &[r#""action":"scanning""#.to_string()]),
+        &["SCANNING..."],
+    );
+    let mut out = String::new();
+    for advert in state.adverts.iter().filter(|a| matches(a)) {
+        if state.verbose {
+            let rssi = if state.show_rssi { format!(r#""rssi":{},"#, advert.rssi) } else { String::new() };
+            out.push_str(&format!(
+                r#"{{"{kind}":{idx},{rssi}"addr":"{}","type":0,"data":"{}"}}"#,
+                advert.addr, advert.data
+            ));
+        } else {
+            let rssi = if state.show_rssi { format!(" RSSI: {},", advert.rssi) } else { String::new() };
+            out.push_str(&format!("[{}]{rssi} Device Data [ADV]: {}", advert.addr, advert.data));
+        }
+        out.push_str("\r\n");
+    }
+    master.write_all(out.as_bytes()).ok();
+    // A scan with a duration ends on its own.
+    if args.split('=').nth(1).is_some() {
+        reply(master, state, &[format!(r#"{{"SE":{idx},"action":"scan completed"}}"#)], &["SCAN COMPLETE"]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::*;
+    use crate::models::bleuio_client::BleuIOClient;
+    use crate::models::hibouair::*;
+    use futures_util::StreamExt;
+
+    const CO2_ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+    const PM_ADVERT: &str = "0201061BFF5B07050322013FBD007D27E000BB00F419000000000000020A02";
+
+    fn adverts() -> Vec<EmulatedAdvert> {
+        vec![
+            EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, CO2_ADVERT),
+            EmulatedAdvert::new("D2:B1:28:3F:42:D4", -74, PM_ADVERT),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_handshake_from_factory_state() {
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        client.init().await.unwrap();
+        client.start_scan(BleuIOCommand::hibouair_scan()).await.unwrap();
+
+        let mut sensors = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while sensors.len() < 2 {
+                match scan_results.next().await {
+                    Some(BleuIOResponse::ScanFindData { data, .. }) => sensors.push(HibouAir::from_hex(&data).unwrap()),
+                    Some(_) => {}
+                    None => panic!("Scan results ended after {} sensors", sensors.len()),
+                }
+            }
+        })
+        .await
+        .expect("Emulator sent no advertisements");
+        assert_eq!(sensors[0].get_board_type(), HibouAirType::Co2Sensor);
+        assert_eq!(sensors[0].get_co2(), 448);
+        assert_eq!(sensors[1].get_board_type(), HibouAirType::PmSensor);
+    }
+
+    #[tokio::test]
+    async fn test_handshake_when_already_verbose() {
+        let emulator = BleuIOEmulator::start_with_state(adverts(), false, true).unwrap();
+        let client = BleuIOClient::open(emulator.slave_path()).unwrap();
+
+        client.init().await.unwrap();
+        let lines = client.send(BleuIOCommand::AtI).await.unwrap();
+        assert!(lines.iter().any(|l| matches!(l, BleuIOResponse::Reply { data, .. } if data["fwVer"] == "2.7.5")));
+    }
+
+    #[tokio::test]
+    async fn test_query_info() {
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let client = BleuIOClient::open(emulator.slave_path()).unwrap();
+
+        client.init().await.unwrap();
+        let info = client.query_info().await.unwrap();
+        assert_eq!(info.get_firmware_version(), Some(FirmwareVersion(2, 7, 5)));
+        assert_eq!(info.hardware, "DA14683");
+        assert_eq!(info.role, GapRole::Dual);
+        assert_eq!(info.mac.as_deref(), Some(EMULATOR_MAC));
+    }
+
+    #[tokio::test]
+    async fn test_scan_target() {
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        client.init().await.unwrap();
+        let target: BleAddress = "D2:B1:28:3F:42:D4".parse().unwrap();
+        client.start_scan(BleuIOCommand::AtScanTarget { targets: vec![target], duration: Some(1) }).await.unwrap();
+
+        let first = scan_results.next().await.unwrap();
+        let BleuIOResponse::ScanTarget { addr, rssi, data, .. } = first else {
+            panic!("Expected an ST line, got {first:?}");
+        };
+        assert_eq!(addr, "D2:B1:28:3F:42:D4");
+        assert_eq!(rssi, Some(-74));
+        assert_eq!(HibouAir::from_hex(&data).unwrap().get_board_type(), HibouAirType::PmSensor);
+        let second = scan_results.next().await.unwrap();
+        assert_eq!(second.get_type(), BleuIOResponseType::ScanEndedResponse);
+    }
+
+    #[tokio::test]
+    async fn test_gatt_read() {
+        use crate::models::gatt::*;
+
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        let mut events = client.take_events().unwrap();
+        client.init().await.unwrap();
+
+        let target: BleAddress = "F5:50:35:CF:B1:ED".parse().unwrap();
+        client.send(BleuIOCommand::AtGapConnect(target)).await.unwrap();
+        let connected = events.next().await.unwrap().get_connection_event();
+        assert!(matches!(connected, Some(ConnectionEvent::Connected { conn_idx: Some(0), .. })));
+
+        let characteristics = parse_characteristics(&client.send(BleuIOCommand::AtGetServices).await.unwrap());
+        assert_eq!(characteristics.len(), 6);
+        let manufacturer = characteristics.iter().find(|c| c.uuid == "2A29").unwrap();
+        let value = parse_read_value(&client.send(BleuIOCommand::AtGattcRead(manufacturer.handle)).await.unwrap());
+        assert_eq!(value.as_deref(), Some(b"Smart Sensor Devices".as_slice()));
+        assert!(client.send(BleuIOCommand::AtGattcRead(0x0030)).await.is_err());
+
+        client.send(BleuIOCommand::AtGapDisconnect).await.unwrap();
+        let disconnected = events.next().await.unwrap().get_connection_event();
+        assert!(matches!(disconnected, Some(ConnectionEvent::Disconnected { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_autoexec() {
+        use crate::models::autoexec::*;
+
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        client.init().await.unwrap();
+
+        assert!(client.read_autoexec().await.unwrap().is_empty());
+        let preset = hibouair_preset(&BleuIOCommand::hibouair_scan());
+        assert_eq!(client.write_autoexec(&preset).await.unwrap(), preset);
+        assert_eq!(client.read_autoexec().await.unwrap(), preset);
+        assert!(client.write_autoexec(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_of_unknown_model() {
+        let emulator = BleuIOEmulator::start(adverts()).unwrap();
+        let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        client.set_model(BleuIOModel::Other);
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        // Scanning with RSSI works as on any dongle.
+        client.init().await.unwrap();
+        client.start_scan(BleuIOCommand::hibouair_scan()).await.unwrap();
+        assert!(matches!(scan_results.next().await, Some(BleuIOResponse::ScanFindData { rssi: Some(-61), .. })));
+
+        let refused = client.read_autoexec().await;
+        assert!(matches!(refused, Err(BleuIOError::Command(BleuIOErrorCode::NotSupported))));
+        assert_eq!(client.query_info().await.unwrap().model, Some(BleuIOModel::Other));
+    }
+
+    #[tokio::test]
+    async fn test_init_from_every_mode() {
+        for (echo, verbose) in [(true, true), (true, false), (false, true), (false, false)] {
+            let emulator = BleuIOEmulator::start_with_state(adverts(), echo, verbose).unwrap();
+            let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+            let mut scan_results = client.take_scan_results().unwrap();
+
+            assert_eq!(client.detect_mode().await.unwrap(), DongleMode { echo, verbose });
+            client.init().await.unwrap();
+            assert_eq!(client.detect_mode().await.unwrap(), DongleMode { echo: false, verbose: true });
+
+            client.start_scan(BleuIOCommand::hibouair_scan()).await.unwrap();
+            let first = scan_results.next().await.unwrap();
+            assert!(matches!(first, BleuIOResponse::ScanFindData { rssi: Some(-61), .. }), "echo {echo}, verbose {verbose}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plain_scan_output() {
+        let emulator = BleuIOEmulator::start_with_state(adverts(), false, false).unwrap();
+        let mut client = BleuIOClient::open(emulator.slave_path()).unwrap();
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        // Scan without switching to verbose; the plain text lines still arrive as scan results.
+        client.send(BleuIOCommand::AtShowRssi(true)).await.unwrap();
+        client.send(BleuIOCommand::hibouair_scan()).await.unwrap();
+        let first = scan_results.next().await.unwrap();
+        let BleuIOResponse::ScanFindData { addr, rssi, data, .. } = first else {
+            panic!("Expected a scan result, got {first:?}");
+        };
+        assert_eq!(addr, "F5:50:35:CF:B1:ED");
+        assert_eq!(rssi, Some(-61));
+        assert_eq!(HibouAir::from_hex(&data).unwrap().get_co2(), 448);
+    }
+}
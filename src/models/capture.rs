@@ -0,0 +1,197 @@
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::time::{sleep, Duration};
+
+// Capture files hold one received line per text line: "<RFC 3339 host timestamp>\t<raw line>".
+
+// Writes every line received from the dongle to a capture file. Clones write to the same
+// file, so the reconnects of one dongle end up in one capture.
+#[derive(Clone)]
+pub struct CaptureWriter {
+    out: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl CaptureWriter {
+    // Create (or truncate) a capture file.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(CaptureWriter { out: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))) })
+    }
+
+    // Append a line stamped with the current host time.
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.write_line_at(Local::now().fixed_offset(), line)
+    }
+
+    // Append a line with a given timestamp. Flushed right away so a crash doesn't lose the tail.
+    pub fn write_line_at(&mut self, timestamp: DateTime<FixedOffset>, line: &str) -> std::io::Result<()> {
+        let mut out = self.out.lock().map_err(|_| std::io::Error::other("capture writer poisoned"))?;
+        writeln!(out, "{}\t{}", timestamp.to_rfc3339_opts(SecondsFormat::Millis, false), line)?;
+        out.flush()
+    }
+
+    // Flush anything still buffered, e.g. before the app exits.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.out.lock().map_err(|_| std::io::Error::other("capture writer poisoned"))?.flush()
+    }
+}
+
+// Return the capture file of one dongle: `path` with the dongle's name added to the file name,
+// e.g. "site.txt" for dongle "4048FDE6" is "site-4048FDE6.txt". Each dongle gets its own file
+// so it can be replayed on its own.
+pub fn capture_path(path: &Path, dongle: &str) -> PathBuf {
+    let dongle: String = dongle
+        .trim_start_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '.' { c } else { '_' })
+        .collect();
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{dongle}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{dongle}"),
+    };
+    path.with_file_name(name)
+}
+
+// One line read back from a capture file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedLine {
+    pub timestamp: DateTime<FixedOffset>,
+    pub line: String,
+}
+
+// Parse one line of a capture file. Returns None for lines that aren't "<timestamp>\t<line>".
+pub fn parse_capture_line(text: &str) -> Option<CapturedLine> {
+    let (timestamp, line) = text.split_once('\t')?;
+    let timestamp = DateTime::parse_from_rfc3339(timestamp).ok()?;
+    Some(CapturedLine { timestamp, line: line.trim_end_matches(['\r', '\n']).to_string() })
+}
+
+// Read a whole capture file, skipping lines that can't be parsed.
+pub fn read_capture<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<CapturedLine>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut lines = Vec::new();
+    for text in reader.lines() {
+        if let Some(captured) = parse_capture_line(&text?) {
+            lines.push(captured);
+        }
+    }
+    Ok(lines)
+}
+
+// Delay before replaying `next` after `prev`. A speed of 2.0 replays twice as fast;
+// 0 (or anything not positive) replays without delays.
+pub fn replay_delay(prev: &CapturedLine, next: &CapturedLine, speed: f64) -> Duration {
+    if speed <= 0.0 || !speed.is_finite() {
+        return Duration::ZERO;
+    }
+    let millis = (next.timestamp - prev.timestamp).num_milliseconds().max(0) as f64 / speed;
+    Duration::from_secs_f64(millis / 1000.0)
+}
+
+// Feed captured lines back as if they came from a dongle. The returned stream can be given to
+// `BleuIOClient::from_stream`; anything written to it is discarded and it reaches EOF after the
+// last line.
+pub fn replay(lines: Vec<CapturedLine>, speed: f64) -> DuplexStream {
+    let (host, dongle) = tokio::io::duplex(64 * 1024);
+    let (mut dongle_rx, mut dongle_tx) = tokio::io::split(dongle);
+
+    tokio::spawn(async move {
+        let mut buf = [0u8; 256];
+        while let Ok(n) = dongle_rx.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut prev: Option<&CapturedLine> = None;
+        for captured in &lines {
+            if let Some(prev) = prev {
+                sleep(replay_delay(prev, captured, speed)).await;
+            }
+            if dongle_tx.write_all(format!("{}\r\n", captured.line).as_bytes()).await.is_err() {
+                return;
+            }
+            prev = Some(captured);
+        }
+        dongle_tx.shutdown().await.ok();
+    });
+
+    host
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::*;
+    use crate::models::bleuio_client::BleuIOClient;
+    use futures_util::StreamExt;
+
+    const SF_LINE: &str = r#"{"SF":38,"addr":"F5:50:35:CF:B1:ED","type":0,"data":"0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002"}"#;
+
+    fn captured(timestamp: &str, line: &str) -> CapturedLine {
+        CapturedLine { timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap(), line: line.to_string() }
+    }
+
+    #[test]
+    fn test_capture_round_trip() {
+        let path = std::env::temp_dir().join(format!("bleuio-capture-{}.txt", std::process::id()));
+        let t0 = DateTime::parse_from_rfc3339("2026-01-20T10:00:00.000+01:00").unwrap();
+
+        let mut writer = CaptureWriter::create(&path).unwrap();
+        writer.write_line_at(t0, r#"{"E":38,"nol":4}"#).unwrap();
+        writer.write_line_at(t0 + chrono::Duration::milliseconds(1500), SF_LINE).unwrap();
+        drop(writer);
+
+        let lines = read_capture(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].timestamp, t0);
+        assert_eq!(lines[1].line, SF_LINE);
+    }
+
+    #[test]
+    fn test_capture_path() {
+        assert_eq!(capture_path(Path::new("site.txt"), "4048FDE6"), PathBuf::from("site-4048FDE6.txt"));
+        assert_eq!(capture_path(Path::new("/tmp/site.txt"), "/dev/ttyACM0"), PathBuf::from("/tmp/site-dev_ttyACM0.txt"));
+        assert_eq!(capture_path(Path::new("site"), "tcp://10.0.0.5:4000"), PathBuf::from("site-tcp___10.0.0.5_4000"));
+    }
+
+    #[test]
+    fn test_parse_capture_line() {
+        assert!(parse_capture_line("not a capture line").is_none());
+        assert!(parse_capture_line("yesterday\tECHO OFF").is_none());
+        let line = parse_capture_line("2026-01-20T10:00:00.250+01:00\tECHO OFF\r").unwrap();
+        assert_eq!(line.line, "ECHO OFF");
+    }
+
+    #[test]
+    fn test_replay_delay() {
+        let a = captured("2026-01-20T10:00:00.000+01:00", "a");
+        let b = captured("2026-01-20T10:00:02.000+01:00", "b");
+        assert_eq!(replay_delay(&a, &b, 1.0), Duration::from_secs(2));
+        assert_eq!(replay_delay(&a, &b, 4.0), Duration::from_millis(500));
+        assert_eq!(replay_delay(&a, &b, 0.0), Duration::ZERO);
+        assert_eq!(replay_delay(&b, &a, 1.0), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_replay_through_client() {
+        let lines = vec![
+            captured("2026-01-20T10:00:00.000+01:00", r#"{"C":38,"cmd":"AT+FINDSCANDATA=FF5B07"}"#),
+            captured("2026-01-20T10:00:00.010+01:00", r#"{"E":38,"nol":4}"#),
+            captured("2026-01-20T10:00:05.000+01:00", SF_LINE),
+        ];
+        let mut client = BleuIOClient::from_stream(replay(lines, 0.0));
+        let mut scan_results = client.take_scan_results().unwrap();
+
+        let first = scan_results.next().await.unwrap();
+        assert!(matches!(first, BleuIOResponse::ScanFindData { idx: 38, .. }));
+        assert!(scan_results.next().await.is_none());
+    }
+}
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::models::bleuio::{find_bleuio_all, BleAddress, BleuIOPort, BleuIOCommand, BleuIOVariant, FirmwareVersion};
+use crate::models::bleuio_client::DongleTarget;
+
+pub const USAGE: &str = "\
+Usage: dxbleuio [options]
+
+Options:
+  --port <path>            serial port of a BleuIO dongle, may be repeated; a dongle shared
+                           over the network is given as tcp://host:port or rfc2217://host:port
+                           (default: every attached dongle)
+  --capture <file>         write every line received from each dongle to <file> with the
+                           dongle's name added to the file name
+  --readings <file>        write every decoded HibouAir reading to <file> as one line of
+                           JSON per reading
+  --replay <file>          replay a capture file instead of opening a serial port
+  --replay-speed <factor>  replay speed, 1 = original timing, 0 = as fast as possible (default: 1)
+  --scan-pattern <hex>     advertising data to scan for (default: FF5B07, HibouAir)
+  --scan-duration <secs>   stop each scan after <secs> seconds (default: scan until stopped)
+  --scan-silence <secs>    restart the scan when nothing is heard for <secs> seconds,
+                           0 = never (default: 60)
+  --target <mac>           only listen to this sensor, may be repeated; uses AT+SCANTARGET
+  --min-firmware <version> warn about dongles with older firmware (default: 2.2.0)
+  --usb-id <vid:pid[=model]>
+                           also treat this USB id as a BleuIO dongle, may be repeated;
+                           model is bleuio or pro; otherwise the dongle only scans, with
+                           no autoexec and no sensor reads
+  --help                   show this text";
+
+// Oldest dongle firmware the app is known to work with (JSON verbose output).
+pub const MIN_FIRMWARE: FirmwareVersion = FirmwareVersion(2, 2, 0);
+
+// What the dongles scan for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanSettings {
+    pub pattern: String,            // hex pattern matched against advertising data
+    pub duration: Option<u32>,      // seconds, None = until stopped
+    pub targets: Vec<BleAddress>,   // when set, only these sensors are scanned
+    pub silence_window: Option<Duration>,   // restart the scan after this long without results
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        ScanSettings {
+            pattern: "FF5B07".to_string(),
+            duration: None,
+            targets: Vec::new(),
+            silence_window: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+impl ScanSettings {
+    // Return the command that starts the scan: AT+SCANTARGET for pinned sensors,
+    // AT+FINDSCANDATA otherwise.
+    pub fn to_command(&self) -> BleuIOCommand {
+        if self.targets.is_empty() {
+            BleuIOCommand::AtFindscandata { pattern: self.pattern.clone(), duration: self.duration }
+        } else {
+            BleuIOCommand::AtScanTarget { targets: self.targets.clone(), duration: self.duration }
+        }
+    }
+}
+
+// Application settings, taken from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    pub ports: Vec<String>,
+    pub capture: Option<PathBuf>,
+    pub readings: Option<PathBuf>,      // where decoded readings are written, as JSON lines
+    pub replay: Option<PathBuf>,
+    pub replay_speed: f64,
+    pub scan: ScanSettings,
+    pub min_firmware: FirmwareVersion,
+    pub usb_ids: Vec<BleuIOVariant>,    // extra VID/PID pairs to recognise as dongles
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            ports: Vec::new(),
+            capture: None,
+            readings: None,
+            replay: None,
+            replay_speed: 1.0,
+            scan: ScanSettings::default(),
+            min_firmware: MIN_FIRMWARE,
+            usb_ids: Vec::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    // Parse command line arguments (without the program name). Err holds a message for the user.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut config = AppConfig::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for {name}"));
+            match arg.as_str() {
+                "--port" => config.ports.push(value("--port")?),
+                "--capture" => config.capture = Some(PathBuf::from(value("--capture")?)),
+                "--readings" => config.readings = Some(PathBuf::from(value("--readings")?)),
+                "--replay" => config.replay = Some(PathBuf::from(value("--replay")?)),
+                "--replay-speed" => {
+                    let speed = value("--replay-speed")?;
+                    config.replay_speed = speed
+                        .parse()
+                        .ok()
+                        .filter(|s: &f64| *s >= 0.0)
+                        .ok_or_else(|| format!("Invalid replay speed: {speed}"))?;
+                }
+                "--scan-pattern" => {
+                    let pattern = value("--scan-pattern")?;
+                    if pattern.is_empty() || pattern.len() % 2 != 0 || hex::decode(&pattern).is_err() {
+                        return Err(format!("Invalid scan pattern: {pattern}"));
+                    }
+                    config.scan.pattern = pattern.to_uppercase();
+                }
+                "--scan-duration" => {
+                    let duration = value("--scan-duration")?;
+                    config.scan.duration = Some(
+                        duration
+                            .parse()
+                            .ok()
+                            .filter(|d: &u32| *d > 0)
+                            .ok_or_else(|| format!("Invalid scan duration: {duration}"))?,
+                    );
+                }
+                "--scan-silence" => {
+                    let secs = value("--scan-silence")?;
+                    let secs: u64 = secs.parse().map_err(|_| format!("Invalid scan silence: {secs}"))?;
+                    config.scan.silence_window = (secs > 0).then(|| Duration::from_secs(secs));
+                }
+                "--target" => config.scan.targets.push(value("--target")?.parse()?),
+                "--min-firmware" => config.min_firmware = value("--min-firmware")?.parse()?,
+                "--usb-id" => config.usb_ids.push(value("--usb-id")?.parse()?),
+                "--help" | "-h" => return Err(USAGE.to_string()),
+                _ => return Err(format!("Unknown option: {arg}\n\n{USAGE}")),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // Return true if the user has to pick a port: nothing was given on the command line and
+    // no BleuIO dongle is attached.
+    pub fn needs_port_selection(&self) -> bool {
+        self.replay.is_none() && self.ports.is_empty() && find_bleuio_all(&self.usb_ids).is_empty()
+    }
+
+    // Return true if sessions follow the attached dongles: nothing was given on the command
+    // line, so dongles plugged in later get a session too.
+    pub fn is_auto_detect(&self) -> bool {
+        self.replay.is_none() && self.ports.is_empty()
+    }
+
+    // Return the dongles to run a scanning session for. Without --replay or --port every
+    // attached BleuIO dongle gets a session, bound to its USB serial number.
+    pub fn targets(&self) -> Vec<DongleTarget> {
+        self.targets_for(&find_bleuio_all(&self.usb_ids))
+    }
+
+    // As targets(), with `attached` as the dongles found on the system.
+    pub fn targets_for(&self, attached: &[BleuIOPort]) -> Vec<DongleTarget> {
+        if let Some(path) = &self.replay {
+            return vec![DongleTarget::Replay { path: path.clone(), speed: self.replay_speed }];
+        }
+        if !self.ports.is_empty() {
+            return self.ports.iter().cloned().map(DongleTarget::Port).collect();
+        }
+
+        attached
+            .iter()
+            .map(|p| match &p.serial_number {
+                Some(serial_number) => DongleTarget::SerialNumber(serial_number.clone()),
+                None => DongleTarget::Port(p.port_name.clone()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::BleuIOModel;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_config_defaults() {
+        let config = AppConfig::from_args(args(&[])).unwrap();
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn test_config_replay() {
+        let config = AppConfig::from_args(args(&["--replay", "site.txt", "--replay-speed", "10", "--readings", "site.jsonl"])).unwrap();
+        assert_eq!(config.targets(), vec![DongleTarget::Replay { path: PathBuf::from("site.txt"), speed: 10.0 }]);
+        assert_eq!(config.readings, Some(PathBuf::from("site.jsonl")));
+    }
+
+    #[test]
+    fn test_config_port_and_capture() {
+        let config = AppConfig::from_args(args(&["--port", "/dev/ttyACM1", "--capture", "out.txt", "--port", "/dev/ttyACM2"])).unwrap();
+        assert_eq!(config.targets(), vec![
+            DongleTarget::Port("/dev/ttyACM1".to_string()),
+            DongleTarget::Port("/dev/ttyACM2".to_string()),
+        ]);
+        assert_eq!(config.capture, Some(PathBuf::from("out.txt")));
+    }
+
+    #[test]
+    fn test_config_auto_detect() {
+        let config = AppConfig::from_args(args(&[])).unwrap();
+        assert!(config.is_auto_detect());
+        let attached = [
+            BleuIOPort { port_name: "/dev/ttyACM0".to_string(), serial_number: Some("4048FDE6".to_string()), model: BleuIOModel::BleuIO },
+            BleuIOPort { port_name: "/dev/ttyACM1".to_string(), serial_number: None, model: BleuIOModel::BleuIOPro },
+        ];
+        assert_eq!(config.targets_for(&attached), vec![
+            DongleTarget::SerialNumber("4048FDE6".to_string()),
+            DongleTarget::Port("/dev/ttyACM1".to_string()),
+        ]);
+        assert!(config.targets_for(&[]).is_empty());
+
+        // Ports given on the command line win over whatever is attached.
+        let config = AppConfig::from_args(args(&["--port", "/dev/ttyUSB0"])).unwrap();
+        assert!(!config.is_auto_detect());
+        assert_eq!(config.targets_for(&attached), vec![DongleTarget::Port("/dev/ttyUSB0".to_string())]);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        assert!(AppConfig::from_args(args(&["--port"])).is_err());
+        assert!(AppConfig::from_args(args(&["--replay-speed", "-1"])).is_err());
+        assert!(AppConfig::from_args(args(&["--replay-speed", "fast"])).is_err());
+        assert!(AppConfig::from_args(args(&["--bogus"])).is_err());
+        assert!(AppConfig::from_args(args(&["--scan-pattern", "FF5B0"])).is_err());
+        assert!(AppConfig::from_args(args(&["--scan-pattern", "nothex"])).is_err());
+        assert!(AppConfig::from_args(args(&["--scan-duration", "0"])).is_err());
+        assert!(AppConfig::from_args(args(&["--target", "F5:50:35"])).is_err());
+        assert!(AppConfig::from_args(args(&["--min-firmware", "two"])).is_err());
+        assert!(AppConfig::from_args(args(&["--scan-silence", "-5"])).is_err());
+    }
+
+    #[test]
+    fn test_config_min_firmware() {
+        assert_eq!(AppConfig::default().min_firmware, MIN_FIRMWARE);
+        let config = AppConfig::from_args(args(&["--min-firmware", "2.7.0"])).unwrap();
+        assert_eq!(config.min_firmware, FirmwareVersion(2, 7, 0));
+    }
+
+    #[test]
+    fn test_config_usb_ids() {
+        use crate::models::bleuio::BleuIOModel;
+
+        let config = AppConfig::from_args(args(&["--usb-id", "2dcf:6003", "--usb-id", "1209:b10e=pro"])).unwrap();
+        assert_eq!(config.usb_ids, vec![
+            BleuIOVariant { vid: 0x2DCF, pid: 0x6003, model: BleuIOModel::Other },
+            BleuIOVariant { vid: 0x1209, pid: 0xB10E, model: BleuIOModel::BleuIOPro },
+        ]);
+        assert!(AppConfig::from_args(args(&["--usb-id", "2dcf"])).is_err());
+    }
+
+    #[test]
+    fn test_config_scan_settings() {
+        let config = AppConfig::default();
+        assert_eq!(config.scan.to_command(), BleuIOCommand::hibouair_scan());
+
+        assert_eq!(config.scan.silence_window, Some(Duration::from_secs(60)));
+        let config = AppConfig::from_args(args(&["--scan-silence", "0"])).unwrap();
+        assert_eq!(config.scan.silence_window, None);
+
+        let config = AppConfig::from_args(args(&["--scan-pattern", "ff5b07050422", "--scan-duration", "30"])).unwrap();
+        assert_eq!(config.scan.to_command().to_command_string(), "AT+FINDSCANDATA=FF5B07050422=30");
+
+        let config = AppConfig::from_args(args(&["--target", "F5:50:35:CF:B1:ED", "--target", "[1]D2:B1:28:3F:42:D4"])).unwrap();
+        assert_eq!(
+            config.scan.to_command().to_command_string(),
+            "AT+SCANTARGET=[1]F5:50:35:CF:B1:ED;[1]D2:B1:28:3F:42:D4"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use chrono::{DateTime, Local};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::models::bleuio::BleuIOResponse;
+
+// Characteristics of the Device Information service (0x180A) and a few other well known
+// ones, with the label shown in the UI.
+const KNOWN_CHARACTERISTICS: [(&str, &str); 9] = [
+    ("2A00", "Device name"),
+    ("2A29", "Manufacturer"),
+    ("2A24", "Model number"),
+    ("2A25", "Serial number"),
+    ("2A27", "Hardware revision"),
+    ("2A26", "Firmware revision"),
+    ("2A28", "Software revision"),
+    ("2A23", "System ID"),
+    ("2A19", "Battery level"),
+];
+
+// Device Information service UUID, the characteristics the asset inventory is after.
+pub const DEVICE_INFORMATION_SERVICE: &str = "180A";
+
+// One characteristic listed by AT+GETSERVICES.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GattCharacteristic {
+    pub service: String,        // 16-bit or 128-bit service UUID, upper case hex
+    pub uuid: String,           // characteristic UUID, upper case hex
+    pub handle: u16,            // value handle, as passed to AT+GATTCREAD
+    pub properties: String,     // e.g. "READ|NOTIFY"
+}
+
+impl GattCharacteristic {
+    pub fn is_readable(&self) -> bool {
+        self.properties.split('|').any(|p| p.trim().eq_ignore_ascii_case("READ"))
+    }
+}
+
+// A value read from a characteristic.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GattValue {
+    pub characteristic: GattCharacteristic,
+    pub value: Vec<u8>,
+}
+
+impl GattValue {
+    // Return the name of the characteristic, or its UUID if it isn't a well known one.
+    pub fn get_label(&self) -> String {
+        KNOWN_CHARACTERISTICS
+            .iter()
+            .find(|(uuid, _)| *uuid == self.characteristic.uuid)
+            .map(|(_, label)| label.to_string())
+            .unwrap_or_else(|| format!("Characteristic {}", self.characteristic.uuid))
+    }
+
+    // Return the value as text when it is printable, otherwise as hex. The battery level is
+    // a single byte percentage.
+    pub fn get_display(&self) -> String {
+        if self.characteristic.uuid == "2A19" && self.value.len() == 1 {
+            return format!("{} %", self.value[0]);
+        }
+        match std::str::from_utf8(&self.value) {
+            Ok(text) if !text.is_empty() && text.chars().all(|c| !c.is_control() || c == '\0') => {
+                text.trim_end_matches('\0').to_string()
+            }
+            _ => hex::encode_upper(&self.value),
+        }
+    }
+
+    pub fn is_device_information(&self) -> bool {
+        self.characteristic.service == DEVICE_INFORMATION_SERVICE
+    }
+}
+
+/* AT+GETSERVICES is expected to reply with one R line per characteristic:
+    {"R":12,"svc":"180A","uuid":"2A29","handle":"0x0011","props":"READ"}
+   The field names are not from the BleuIO command reference, which doesn't describe the
+   verbose output of this command, nor from a capture of a real dongle; the emulator replies
+   this way. Lines without them are skipped, and a reply with none at all is reported as a
+   failed read rather than as a sensor without characteristics.
+*/
+pub fn parse_characteristics(lines: &[BleuIOResponse]) -> Vec<GattCharacteristic> {
+    lines
+        .iter()
+        .filter_map(|line| match line {
+            BleuIOResponse::Reply { data, .. } => Some(GattCharacteristic {
+                service: data.get("svc")?.as_str()?.to_uppercase(),
+                uuid: data.get("uuid")?.as_str()?.to_uppercase(),
+                handle: parse_handle(data.get("handle")?)?,
+                properties: data.get("props").and_then(Value::as_str).unwrap_or_default().to_uppercase(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/* AT+GATTCREAD replies with the value as hex:
+    {"R":13,"hdl":"0x0011","hex":"0x536D61727420","size":6,"ascii":"Smart "}
+*/
+pub fn parse_read_value(lines: &[BleuIOResponse]) -> Option<Vec<u8>> {
+    lines.iter().find_map(|line| match line {
+        BleuIOResponse::Reply { data, .. } => {
+            let hex = data.get("hex")?.as_str()?;
+            hex::decode(hex.trim_start_matches("0x")).ok()
+        }
+        _ => None,
+    })
+}
+
+// Handles come as "0x0011" or as a plain number.
+fn parse_handle(value: &Value) -> Option<u16> {
+    match value {
+        Value::String(s) => u16::from_str_radix(s.trim_start_matches("0x"), 16).ok(),
+        Value::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+        _ => None,
+    }
+}
+
+// Progress of reading one sensor over a GATT connection, reported by the session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GattEvent {
+    Connecting(String),             // sensor address; the scan is stopped and AT+GAPCONNECT sent
+    Connected(String),
+    Value(String, GattValue),       // a characteristic was read
+    Disconnected(String, String),   // sensor address, reason
+    Failed(String, String),         // sensor address, what went wrong
+}
+
+impl GattEvent {
+    pub fn get_addr(&self) -> &str {
+        match self {
+            GattEvent::Connecting(addr)
+            | GattEvent::Connected(addr)
+            | GattEvent::Value(addr, _)
+            | GattEvent::Disconnected(addr, _)
+            | GattEvent::Failed(addr, _) => addr,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GattStatus {
+    Connecting,
+    Reading,
+    Done,
+    Failed(String),
+}
+
+// What was read from a sensor over GATT, shown in its detail view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorDetails {
+    pub status: GattStatus,
+    pub values: Vec<GattValue>,
+    pub updated: DateTime<Local>,
+}
+
+impl SensorDetails {
+    pub fn new(now: DateTime<Local>) -> Self {
+        SensorDetails { status: GattStatus::Connecting, values: Vec::new(), updated: now }
+    }
+
+    // Return true while a connection is being made or read.
+    pub fn is_busy(&self) -> bool {
+        matches!(self.status, GattStatus::Connecting | GattStatus::Reading)
+    }
+
+    // Values of the Device Information service.
+    pub fn get_device_information(&self) -> Vec<&GattValue> {
+        self.values.iter().filter(|v| v.is_device_information()).collect()
+    }
+
+    // Every other readable characteristic.
+    pub fn get_other_values(&self) -> Vec<&GattValue> {
+        self.values.iter().filter(|v| !v.is_device_information()).collect()
+    }
+
+    fn apply(&mut self, event: GattEvent, now: DateTime<Local>) {
+        self.updated = now;
+        match event {
+            GattEvent::Connecting(_) => {
+                self.status = GattStatus::Connecting;
+                self.values.clear();
+            }
+            GattEvent::Connected(_) => self.status = GattStatus::Reading,
+            GattEvent::Value(_, value) => self.values.push(value),
+            // A disconnect after reading is the normal end; while reading it is a failure.
+            GattEvent::Disconnected(_, reason) => {
+                self.status = match self.status {
+                    GattStatus::Reading if self.values.is_empty() => GattStatus::Failed(format!("Disconnected: {reason}")),
+                    GattStatus::Failed(_) => return,
+                    _ => GattStatus::Done,
+                }
+            }
+            GattEvent::Failed(_, reason) => self.status = GattStatus::Failed(reason),
+        }
+    }
+}
+
+// Update the details of the sensor `event` is about.
+pub fn apply_gatt_event(details: &mut HashMap<String, SensorDetails>, event: GattEvent, now: DateTime<Local>) {
+    details
+        .entry(event.get_addr().to_string())
+        .or_insert_with(|| SensorDetails::new(now))
+        .apply(event, now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::parse_bleuio_response;
+
+    fn lines(json: &[&str]) -> Vec<BleuIOResponse> {
+        json.iter().map(|l| parse_bleuio_response(l).unwrap()).collect()
+    }
+
+    fn characteristic(service: &str, uuid: &str, handle: u16) -> GattCharacteristic {
+        GattCharacteristic { service: service.to_string(), uuid: uuid.to_string(), handle, properties: "READ".to_string() }
+    }
+
+    #[test]
+    fn test_parse_characteristics() {
+        let chars = parse_characteristics(&lines(&[
+            r#"{"C":12,"cmd":"AT+GETSERVICES"}"#,
+            r#"{"A":12,"err":0,"errMsg":"ok"}"#,
+            r#"{"R":12,"svc":"180a","uuid":"2a29","handle":"0x0011","props":"READ"}"#,
+            r#"{"R":12,"svc":"180F","uuid":"2A19","handle":27,"props":"READ|NOTIFY"}"#,
+            r#"{"R":12,"svc":"FFF0","uuid":"FFF1","handle":"0x0030","props":"WRITE"}"#,
+            r#"{"E":12,"nol":6}"#,
+        ]));
+        assert_eq!(chars.len(), 3);
+        assert_eq!(chars[0], GattCharacteristic {
+            service: "180A".to_string(),
+            uuid: "2A29".to_string(),
+            handle: 0x11,
+            properties: "READ".to_string(),
+        });
+        assert_eq!(chars[1].handle, 27);
+        assert!(chars[1].is_readable());
+        assert!(!chars[2].is_readable());
+    }
+
+    #[test]
+    fn test_parse_read_value() {
+        let value = parse_read_value(&lines(&[
+            r#"{"C":13,"cmd":"AT+GATTCREAD=0011"}"#,
+            r#"{"A":13,"err":0,"errMsg":"ok"}"#,
+            r#"{"R":13,"hdl":"0x0011","hex":"0x536D61727420","size":6,"ascii":"Smart "}"#,
+            r#"{"E":13,"nol":4}"#,
+        ]));
+        assert_eq!(value.as_deref(), Some(b"Smart ".as_slice()));
+        assert_eq!(parse_read_value(&lines(&[r#"{"E":13,"nol":1}"#])), None);
+    }
+
+    #[test]
+    fn test_value_display() {
+        let value = |uuid: &str, value: &[u8]| GattValue { characteristic: characteristic("180A", uuid, 1), value: value.to_vec() };
+        assert_eq!(value("2A29", b"Smart Sensor Devices").get_label(), "Manufacturer");
+        assert_eq!(value("2A29", b"Smart Sensor Devices").get_display(), "Smart Sensor Devices");
+        assert_eq!(value("2A26", b"1.2.3\0").get_display(), "1.2.3");
+        assert_eq!(value("2A23", &[0x01, 0xFF, 0x00]).get_display(), "01FF00");
+        assert_eq!(value("2A19", &[87]).get_display(), "87 %");
+        assert_eq!(value("FFF1", &[1]).get_label(), "Characteristic FFF1");
+    }
+
+    #[test]
+    fn test_apply_gatt_events() {
+        let now = Local::now();
+        let addr = "D2:B1:28:3F:42:D4".to_string();
+        let manufacturer = GattValue { characteristic: characteristic("180A", "2A29", 0x11), value: b"Smart Sensor Devices".to_vec() };
+        let battery = GattValue { characteristic: characteristic("180F", "2A19", 0x1B), value: vec![87] };
+
+        let mut details = HashMap::new();
+        apply_gatt_event(&mut details, GattEvent::Connecting(addr.clone()), now);
+        assert!(details[&addr].is_busy());
+        apply_gatt_event(&mut details, GattEvent::Connected(addr.clone()), now);
+        assert_eq!(details[&addr].status, GattStatus::Reading);
+        apply_gatt_event(&mut details, GattEvent::Value(addr.clone(), manufacturer.clone()), now);
+        apply_gatt_event(&mut details, GattEvent::Value(addr.clone(), battery.clone()), now);
+        apply_gatt_event(&mut details, GattEvent::Disconnected(addr.clone(), "local host".to_string()), now);
+        assert_eq!(details[&addr].status, GattStatus::Done);
+        assert_eq!(details[&addr].get_device_information(), vec![&manufacturer]);
+        assert_eq!(details[&addr].get_other_values(), vec![&battery]);
+
+        // Reading again starts over; losing the link before anything was read is a failure.
+        apply_gatt_event(&mut details, GattEvent::Connecting(addr.clone()), now);
+        assert!(details[&addr].values.is_empty());
+        apply_gatt_event(&mut details, GattEvent::Connected(addr.clone()), now);
+        apply_gatt_event(&mut details, GattEvent::Disconnected(addr.clone(), "8".to_string()), now);
+        assert_eq!(details[&addr].status, GattStatus::Failed("Disconnected: 8".to_string()));
+
+        apply_gatt_event(&mut details, GattEvent::Failed(addr.clone(), "No connection".to_string()), now);
+        assert_eq!(details[&addr].status, GattStatus::Failed("No connection".to_string()));
+        assert!(!details[&addr].is_busy());
+    }
+}
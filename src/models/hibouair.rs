@@ -1,6 +1,9 @@
 use hex;
-use zerocopy::{FromBytes, Unaligned, Immutable, KnownLayout};
+use serde::{Deserialize, Serialize};
+use zerocopy::byteorder::{big_endian, little_endian};
+use zerocopy::{FromBytes, FromZeros, IntoBytes, Unaligned, Immutable, KnownLayout};
 
+#[derive(Debug, Clone, PartialEq, Copy)]
 pub enum VocType {
     Old = 0,
     Resistance = 1,
@@ -8,9 +11,99 @@ pub enum VocType {
     Iaq = 3,
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+impl TryFrom<u8> for VocType {
+    type Error = &'static str;
+
+    fn try_from(value: u8) -> std::result::Result<Self, Self::Error> {
+        match value {
+            0 => Ok(VocType::Old),
+            1 => Ok(VocType::Resistance),
+            2 => Ok(VocType::Ppm),
+            3 => Ok(VocType::Iaq),
+            _ => Err("Unknown VOC type"),
+        }
+    }
+}
+
+// A VOC value in the unit of the sensor that measured it, as told by the VOC type byte.
+// Serialized as {"type": "ppm", "value": 0.62}; values without a documented unit are marked
+// raw, as in {"type": "resistance_raw", "value": 125}.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum VocReading {
+    #[serde(rename = "old_raw")]
+    Old(u16),           // index from older firmware as sent, without unit
+    #[serde(rename = "resistance_raw")]
+    Resistance(u16),    // gas sensor resistance as sent, its scale and unit aren't documented
+    Ppm(f64),           // total VOC in ppm, sent as ppm * 100
+    Iaq(u16),           // indoor air quality index, 0-500
+}
+
+impl VocReading {
+    pub fn new(voc_type: VocType, raw: u16) -> Self {
+        match voc_type {
+            VocType::Old => VocReading::Old(raw),
+            VocType::Resistance => VocReading::Resistance(raw),
+            VocType::Ppm => VocReading::Ppm(raw as f64 / 100.0),
+            VocType::Iaq => VocReading::Iaq(raw),
+        }
+    }
+
+    // Return the VOC type and the value as sent.
+    pub fn to_raw(&self) -> (VocType, u16) {
+        match self {
+            VocReading::Old(v) => (VocType::Old, *v),
+            VocReading::Resistance(v) => (VocType::Resistance, *v),
+            VocReading::Ppm(v) => (VocType::Ppm, scale(*v, 100.0) as u16),
+            VocReading::Iaq(v) => (VocType::Iaq, *v),
+        }
+    }
+
+    // Return the value in its unit.
+    pub fn get_value(&self) -> f64 {
+        match self {
+            VocReading::Old(v) | VocReading::Resistance(v) | VocReading::Iaq(v) => *v as f64,
+            VocReading::Ppm(v) => *v,
+        }
+    }
+
+    pub fn get_unit(&self) -> &'static str {
+        match self {
+            VocReading::Old(_) | VocReading::Resistance(_) => "raw",
+            VocReading::Ppm(_) => "ppm",
+            VocReading::Iaq(_) => "IAQ",
+        }
+    }
+
+    // Return the value with its unit.
+    pub fn get_view(&self) -> String {
+        match self {
+            VocReading::Old(v) | VocReading::Resistance(v) => format!("{v} raw"),
+            VocReading::Ppm(v) => format!("{v:.2} ppm"),
+            VocReading::Iaq(v) => format!("{v} IAQ"),
+        }
+    }
+
+    // Return the air quality category of an IAQ index (Bosch BME680 scale).
+    pub fn get_iaq_label(&self) -> Option<&'static str> {
+        let VocReading::Iaq(iaq) = self else {
+            return None;
+        };
+        Some(match iaq {
+            0..=50 => "Excellent",
+            51..=100 => "Good",
+            101..=150 => "Lightly polluted",
+            151..=200 => "Moderately polluted",
+            201..=250 => "Heavily polluted",
+            251..=350 => "Severely polluted",
+            _ => "Extremely polluted",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum HibouAirType {
-    Unknown = 0x00,
     TempHumSensor = 0x02,
     PmSensor = 0x03,
     Co2Sensor = 0x04,
@@ -22,6 +115,8 @@ pub enum HibouAirType {
     DuoMaster = 0x0A,
     DuoSlave = 0x0B,
     Matrix = 0x14,
+    #[serde(other)]     // board types added by a newer app read as unknown
+    Unknown = 0x00,
 }
 
 impl TryFrom<i64> for HibouAirType {
@@ -46,66 +141,131 @@ impl TryFrom<i64> for HibouAirType {
     }
 }
 
-// #[derive(Debug, Clone, PartialEq, Copy)]
-#[repr(C, packed)]
-#[derive(FromBytes, Unaligned, Immutable, KnownLayout, Debug, Clone, Copy, PartialEq)]
-pub struct HibouAir {
-    mfid: u16,          // the manufacturer id of the device
-    beacon_nr: u8,      // type of beacon
-    board_type: u8,     // type of device
-    board_id: [u8;3],   // unique board id
-    als: u16,           // ambient light sensor
-    bar: u16,           // pressure
-    temp: u16,          // temperature
-    hum: u16,           // humidity
-    voc: u16,           // volatile organic compounds
-    pm1_0: u16,         // particle matter PM1.0
-    pm2_5: u16,         // particle matter PM2.5
-    pm10: u16,          // particle matter PM10.0
-    co2: u16,           // carbon dioxide
-    voc_type: u8,       // 0 = old, 1 = resistance, 2 = ppm, 3 = IAQ
-}
-
-impl HibouAir {
-    /// Tar en hex-sträng och försöker konvertera den till en HibouAir-struct
-    pub fn from_hex(hex_str: &str) -> std::result::Result<Self, String> {
-        let bytes = hex::decode(hex_str)
-            .map_err(|e| format!("Invalid hex string: {e}"))?;
-
-        // Walk through BLE AD structures:
-        // [len][type][payload...]
-        let mut i = 0usize;
-        while i < bytes.len() {
-            let len = bytes[i] as usize;
-            if len == 0 { break; }
-
-            let end = i + 1 + len;
-            if end > bytes.len() { break; }
+// Every value of the full beacon.
+const ALL_MEASUREMENTS: &[Measurement] = {
+    use Measurement::*;
+    &[Co2, Voc, Pm10, Pm2_5, Pm1_0, Hum, Temp, Bar, Light]
+};
+
+impl HibouAirType {
+    // Return the measurements the board type reports, in the order they are shown. Only the
+    // CO2 and PM lists are known, from the panels this app started with; without a published
+    // list for the other types they show every value of the full beacon rather than a guess.
+    pub fn get_measurements(&self) -> &'static [Measurement] {
+        use Measurement::*;
+        match self {
+            HibouAirType::PmSensor => &[Pm10, Pm2_5, Pm1_0, Voc, Hum, Temp, Bar],
+            HibouAirType::Co2Sensor => &[Co2, Voc, Hum, Temp, Bar, Light],
+            HibouAirType::Unknown => &[],
+            _ => ALL_MEASUREMENTS,
+        }
+    }
 
-            let ad_type = bytes[i + 1];
+    // Return true if get_measurements lists only the values the board type has.
+    pub fn has_known_measurements(&self) -> bool {
+        matches!(self, HibouAirType::PmSensor | HibouAirType::Co2Sensor | HibouAirType::Unknown)
+    }
+}
 
-            // Manufacturer specific data
-            if ad_type == 0xFF && len >= 3 {
-                // Company ID is 2 bytes little-endian
-                let company_lo = bytes[i + 2];
-                let company_hi = bytes[i + 3];
+// A value a HibouAir board reports in its full beacon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Measurement {
+    Co2,
+    Voc,                // unit depends on the VOC type, see VocReading
+    Pm1_0,
+    Pm2_5,
+    Pm10,
+    Temp,
+    Hum,
+    Bar,
+    Light,
+}
 
-                // 0x075B => bytes = 5B 07
-                if company_lo == 0x5B && company_hi == 0x07 {
-                    // Start struct at company id (same as your old bytes[5..] assumption)
-                    let mfg = &bytes[i + 2..end];
+impl Measurement {
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            Measurement::Co2 => "CO2",
+            Measurement::Voc => "VOC",
+            Measurement::Pm1_0 => "PM1.0",
+            Measurement::Pm2_5 => "PM2.5",
+            Measurement::Pm10 => "PM10",
+            Measurement::Temp => "Temp",
+            Measurement::Hum => "Humidity",
+            Measurement::Bar => "Pressure",
+            Measurement::Light => "Light",
+        }
+    }
 
-                    let (data, _rest) = Self::read_from_prefix(mfg)
-                        .map_err(|_| "Manufacturer data too short for HibouAir format".to_string())?;
+    // Return the value with its unit, as shown on the sensor panels.
+    pub fn format_value(&self, value: f64) -> String {
+        match self {
+            Measurement::Co2 => format!("{value:.0} ppm"),
+            Measurement::Voc => format!("{value:.1}"),
+            Measurement::Pm1_0 | Measurement::Pm2_5 | Measurement::Pm10 => format!("{value:.1} μg/m³"),
+            Measurement::Temp => format!("{value:.1} °C"),
+            Measurement::Hum => format!("{value:.0} %rh"),
+            Measurement::Bar => format!("{value:.0} hPA"),
+            Measurement::Light => format!("{value:.0} Lux"),
+        }
+    }
+}
 
-                    return Ok(data);
-                }
-            }
+/* Full beacon (0x05): the manufacturer data of the advertisement from the company id on.
+   Every field has its byte order spelled out, so decoding doesn't depend on the host.
+    offset  size  field       encoding
+     0      2     mfid        u16 little-endian, 0x075B
+     2      1     beacon_nr   u8
+     3      1     board_type  u8, HibouAirType
+     4      3     board_id    3 bytes, most significant first
+     7      2     als         u16 little-endian, lux
+     9      2     bar         u16 little-endian, hPa * 10
+    11      2     temp        i16 little-endian, °C * 10
+    13      2     hum         u16 little-endian, %rh * 10
+    15      2     voc         u16 little-endian, scaled by voc_type
+    17      2     pm1_0       u16 little-endian, μg/m³ * 10
+    19      2     pm2_5       u16 little-endian, μg/m³ * 10
+    21      2     pm10        u16 little-endian, μg/m³ * 10
+    23      2     co2         u16 big-endian, ppm
+    25      1     voc_type    u8, VocType
+   26 bytes in all, e.g.
+    5B07 05 04 22005A 0000 BA27 C600 1701 3E00 0000 0000 0000 01C0 02
+*/
+#[repr(C)]
+#[derive(FromBytes, IntoBytes, Unaligned, Immutable, KnownLayout, Debug, Clone, Copy, PartialEq)]
+pub struct HibouAir {
+    mfid: little_endian::U16,   // the manufacturer id of the device
+    beacon_nr: u8,              // type of beacon
+    board_type: u8,             // type of device
+    board_id: [u8;3],           // unique board id
+    als: little_endian::U16,    // ambient light sensor
+    bar: little_endian::U16,    // pressure
+    temp: little_endian::I16,   // temperature
+    hum: little_endian::U16,    // humidity
+    voc: little_endian::U16,    // volatile organic compounds
+    pm1_0: little_endian::U16,  // particle matter PM1.0
+    pm2_5: little_endian::U16,  // particle matter PM2.5
+    pm10: little_endian::U16,   // particle matter PM10.0
+    co2: big_endian::U16,       // carbon dioxide
+    voc_type: u8,               // 0 = old, 1 = resistance, 2 = ppm, 3 = IAQ
+}
 
-            i = end;
-        }
+impl HibouAir {
+    // A full beacon from `board_id` with every measurement zero; fill it in with the setters.
+    pub fn new(board_type: HibouAirType, board_id: u32) -> Self {
+        let mut data = HibouAir::new_zeroed();
+        data.mfid.set(HIBOUAIR_MFID);
+        data.beacon_nr = FULL_BEACON;
+        data.board_type = board_type as u8;
+        data.board_id = board_id_to_bytes(board_id);
+        data
+    }
 
-        Err("No HibouAir manufacturer data (0xFF, company 0x075B) found in advertisement".to_string())
+    /// Tar en hex-sträng och försöker konvertera den till en HibouAir-struct
+    pub fn from_hex(hex_str: &str) -> std::result::Result<Self, String> {
+        let mfg = manufacturer_data(hex_str)?;
+        let (data, _rest) = Self::read_from_prefix(&mfg)
+            .map_err(|_| "Manufacturer data too short for HibouAir format".to_string())?;
+        Ok(data)
     }
 
     #[cfg(feature = "using_ble")]
@@ -121,23 +281,37 @@ impl HibouAir {
         Ok(hibouair)
     }
 
+    // Return the manufacturer data, the inverse of read_from_prefix.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    // Return a whole advertisement carrying this beacon, as from_hex takes it: the flags
+    // structure followed by the manufacturer data.
+    pub fn to_hex(&self) -> String {
+        let mfg = self.to_bytes();
+        let mut advert = vec![0x02, 0x01, 0x06, mfg.len() as u8 + 1, 0xFF];
+        advert.extend(mfg);
+        hex::encode_upper(advert)
+    }
+
     // Return a string representation of the HibouAir struct.
     pub fn to_string(&self) -> String {
         format!(
             "HibouAir(mfid: {}, beacon_nr: {}, board_type: {}, board_id: {:02X?}, als: {}, bar: {}, temp: {}, hum: {}, voc: {}, pm1_0: {}, pm2_5: {}, pm10: {}, co2: {}, voc_type: {})",
-            {self.mfid},
+            self.mfid.get(),
             self.beacon_nr,
             self.board_type,
             self.board_id,
-            {self.als},
-            {self.bar},
-            {self.temp},
-            {self.hum},
-            {self.voc},
-            {self.pm1_0},
-            {self.pm2_5},
-            {self.pm10},
-            {self.co2},
+            self.als.get(),
+            self.bar.get(),
+            self.temp.get(),
+            self.hum.get(),
+            self.voc.get(),
+            self.pm1_0.get(),
+            self.pm2_5.get(),
+            self.pm10.get(),
+            self.co2.get(),
             self.voc_type
         )
     }
@@ -146,7 +320,7 @@ impl HibouAir {
 
     // Return MFID of device.
     pub fn get_mfid(&self) -> u16 {
-        self.mfid
+        self.mfid.get()
     }
 
     // Return beacon number.
@@ -179,7 +353,7 @@ impl HibouAir {
 
     // Return board ID as u32.
     pub fn get_id(&self) -> u32 {
-        ((self.board_id[0] as u32) << 16) | ((self.board_id[1] as u32) << 8) | (self.board_id[2] as u32)
+        board_id_to_u32(self.board_id)
     }
 
     // Return board ID as hex string.
@@ -189,36 +363,41 @@ impl HibouAir {
 
     // Return ambient light sensor value.
     pub fn get_als(&self) -> u16 {
-        self.als
+        self.als.get()
     }
 
     // Return barometric pressure value.
     pub fn get_bar(&self) -> f64 {
-        self.bar as f64 / 10.0
+        self.bar.get() as f64 / 10.0
     }
 
     // Return temperature value.
     pub fn get_temp(&self) -> f64 {
-        (self.temp as i16) as f64 / 10.0
+        self.temp.get() as f64 / 10.0
     }
 
     // Return humidity value.
     pub fn get_hum(&self) -> f64 {
-        self.hum as f64 / 10.0
+        self.hum.get() as f64 / 10.0
     }
 
     // Return CO2 value.
     pub fn get_co2(&self) -> u16 {
-        self.co2.swap_bytes()
+        self.co2.get()
     }
 
-    // Return VOC value.
+    // Return VOC value in the unit of its VOC type, 0 if the sensor doesn't supply one.
     pub fn get_voc(&self) -> f64 {
-        let mut v: f64 = self.voc as f64 ;
-        if self.voc_type == 2 {
-            v = v / 100.0;
+        self.get_voc_reading().map(|v| v.get_value()).unwrap_or(0.0)
+    }
+
+    // Return VOC reading, None if the board type has no VOC sensor or the VOC type is unknown.
+    pub fn get_voc_reading(&self) -> Option<VocReading> {
+        if !self.get_board_type().get_measurements().contains(&Measurement::Voc) {
+            return None;
         }
-        v
+        let voc_type = VocType::try_from(self.voc_type).ok()?;
+        Some(VocReading::new(voc_type, self.voc.get()))
     }
 
     // Return VOC type.
@@ -228,40 +407,154 @@ impl HibouAir {
 
     // Return VOC unit as string.
     pub fn get_voc_unit(&self) -> String {
-        // println!("Voc type: {}", self.voc_type);
-        match self.voc_type {
-            0 => "".to_string(),
-            1 => "".to_string(),
-            2 => "ppm".to_string(),
-            3 => "IAQ".to_string(),
-            _ => "".to_string(),
-        }
+        self.get_voc_reading().map(|v| v.get_unit()).unwrap_or_default().to_string()
     }
 
-    // Return VOC value with unit as string.
+    // Return VOC value with unit as string, empty if the sensor doesn't supply one.
     pub fn get_voc_view(&self) -> String {
-        match self.voc_type {
-            0 => "".to_string(),
-            1 => "".to_string(),
-            2 => format!("{:.1} {}",self.get_voc(), self.get_voc_unit()),
-            3 => format!("{:.1} {}",self.get_voc(), self.get_voc_unit()),
-            _ => "".to_string(),
-        }
+        self.get_voc_reading().map(|v| v.get_view()).unwrap_or_default()
     }
 
     // Return PM1.0 value.
     pub fn get_pm1_0(&self) -> f64 {
-        self.pm1_0 as f64 / 10.0
+        self.pm1_0.get() as f64 / 10.0
     }
 
     // Return PM2.5 value.
     pub fn get_pm2_5(&self) -> f64 {
-        self.pm2_5 as f64 / 10.0
+        self.pm2_5.get() as f64 / 10.0
     }
 
     // Return PM10 value.
     pub fn get_pm10(&self) -> f64 {
-        self.pm10 as f64 / 10.0
+        self.pm10.get() as f64 / 10.0
+    }
+
+    // Setters, taking values in the units the getters return.
+
+    // Set ambient light in lux.
+    pub fn set_als(&mut self, als: u16) {
+        self.als.set(als);
+    }
+
+    // Set barometric pressure in hPa.
+    pub fn set_bar(&mut self, bar: f64) {
+        self.bar.set(scale(bar, 10.0) as u16);
+    }
+
+    // Set temperature in °C.
+    pub fn set_temp(&mut self, temp: f64) {
+        self.temp.set(scale(temp, 10.0) as i16);
+    }
+
+    // Set humidity in %rh.
+    pub fn set_hum(&mut self, hum: f64) {
+        self.hum.set(scale(hum, 10.0) as u16);
+    }
+
+    // Set CO2 in ppm.
+    pub fn set_co2(&mut self, co2: u16) {
+        self.co2.set(co2);
+    }
+
+    // Set VOC value and type.
+    pub fn set_voc(&mut self, voc: VocReading) {
+        let (voc_type, raw) = voc.to_raw();
+        self.voc_type = voc_type as u8;
+        self.voc.set(raw);
+    }
+
+    // Set particle matter PM1.0, PM2.5 and PM10 in μg/m³.
+    pub fn set_pm(&mut self, pm1_0: f64, pm2_5: f64, pm10: f64) {
+        self.pm1_0.set(scale(pm1_0, 10.0) as u16);
+        self.pm2_5.set(scale(pm2_5, 10.0) as u16);
+        self.pm10.set(scale(pm10, 10.0) as u16);
+    }
+}
+
+fn board_id_to_u32(board_id: [u8; 3]) -> u32 {
+    ((board_id[0] as u32) << 16) | ((board_id[1] as u32) << 8) | (board_id[2] as u32)
+}
+
+fn board_id_to_bytes(board_id: u32) -> [u8; 3] {
+    [(board_id >> 16) as u8, (board_id >> 8) as u8, board_id as u8]
+}
+
+// Fixed point value as sent, e.g. 19.8 °C with factor 10 is 198. Out of range values saturate.
+fn scale(value: f64, factor: f64) -> f64 {
+    (value * factor).round()
+}
+
+// Return the manufacturer specific data of the HibouAir in an advertisement, starting at the
+// company id.
+fn manufacturer_data(hex_str: &str) -> std::result::Result<Vec<u8>, String> {
+    let bytes = hex::decode(hex_str)
+        .map_err(|e| format!("Invalid hex string: {e}"))?;
+
+    // Walk through BLE AD structures:
+    // [len][type][payload...]
+    let mut i = 0usize;
+    while i < bytes.len() {
+        let len = bytes[i] as usize;
+        if len == 0 { break; }
+
+        let end = i + 1 + len;
+        if end > bytes.len() { break; }
+
+        let ad_type = bytes[i + 1];
+
+        // Manufacturer specific data, company ID 0x075B is 5B 07 (little-endian)
+        if ad_type == 0xFF && len >= 3 && bytes[i + 2] == 0x5B && bytes[i + 3] == 0x07 {
+            return Ok(bytes[i + 2..end].to_vec());
+        }
+
+        i = end;
+    }
+
+    Err("No HibouAir manufacturer data (0xFF, company 0x075B) found in advertisement".to_string())
+}
+
+// Company id of Smart Sensor Devices, the maker of HibouAir.
+pub const HIBOUAIR_MFID: u16 = 0x075B;
+
+// Beacon number of the full beacon, the byte after the company id.
+pub const FULL_BEACON: u8 = 0x05;
+
+// Company id, beacon number, board type and board id, common to every beacon.
+const BEACON_HEADER_LEN: usize = 7;
+
+// A HibouAir advertisement. Only the full beacon is decoded. Sensors send other beacon numbers
+// too (0x04 and 0x06 have been seen), but without a published layout for them only their
+// header is read, so the sensor can still be told apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HibouAirBeacon {
+    Full(HibouAir),
+    Unknown(u8, u32),                   // beacon number, board id
+}
+
+impl HibouAirBeacon {
+    pub fn from_hex(hex_str: &str) -> std::result::Result<Self, String> {
+        let mfg = manufacturer_data(hex_str)?;
+        if mfg.len() < BEACON_HEADER_LEN {
+            return Err("Manufacturer data too short for a HibouAir beacon".to_string());
+        }
+        let beacon_nr = mfg[2];
+        let board_id = board_id_to_u32([mfg[4], mfg[5], mfg[6]]);
+
+        match beacon_nr {
+            FULL_BEACON => HibouAir::read_from_prefix(&mfg)
+                .map(|(data, _)| HibouAirBeacon::Full(data))
+                .map_err(|_| format!("Manufacturer data too short for beacon {beacon_nr:#04x}")),
+            _ => Ok(HibouAirBeacon::Unknown(beacon_nr, board_id)),
+        }
+    }
+
+    // Return board ID of the sensor that sent the beacon.
+    pub fn get_id(&self) -> u32 {
+        match self {
+            HibouAirBeacon::Full(data) => data.get_id(),
+            HibouAirBeacon::Unknown(_, id) => *id,
+        }
     }
 }
 
@@ -293,6 +586,121 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_hibouair_round_trip() {
+        let hex_str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let hibouair = HibouAir::from_hex(hex_str).unwrap();
+        assert_eq!(hibouair.to_hex(), hex_str);
+        assert_eq!(hibouair.to_bytes().len(), std::mem::size_of::<HibouAir>());
+        assert_eq!(std::mem::size_of::<HibouAir>(), 26);
+
+        // Every field set to a value that shows up in the wrong place if the layout is off.
+        let mut data = HibouAir::new(HibouAirType::Matrix, 0x123456);
+        data.set_als(0x1234);
+        data.set_bar(1013.2);
+        data.set_temp(-12.3);
+        data.set_hum(45.6);
+        data.set_voc(VocReading::Iaq(123));
+        data.set_pm(1.2, 3.4, 5.6);
+        data.set_co2(0x0456);
+        assert_eq!(
+            data.to_hex(),
+            "0201061BFF5B0705141234563412942785FFC8017B000C0022003800045603"
+        );
+
+        let decoded = HibouAir::from_hex(&data.to_hex()).unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(decoded.get_mfid(), HIBOUAIR_MFID);
+        assert_eq!(decoded.get_beacon_nr(), FULL_BEACON);
+        assert_eq!(decoded.get_board_type(), HibouAirType::Matrix);
+        assert_eq!(decoded.get_id(), 0x123456);
+        assert_eq!(decoded.get_als(), 0x1234);
+        assert_eq!(decoded.get_bar(), 1013.2);
+        assert_eq!(decoded.get_temp(), -12.3);
+        assert_eq!(decoded.get_hum(), 45.6);
+        assert_eq!(decoded.get_voc_reading(), Some(VocReading::Iaq(123)));
+        assert_eq!(decoded.get_pm1_0(), 1.2);
+        assert_eq!(decoded.get_pm2_5(), 3.4);
+        assert_eq!(decoded.get_pm10(), 5.6);
+        assert_eq!(decoded.get_co2(), 0x0456);
+
+        for voc in [VocReading::Old(7), VocReading::Resistance(125), VocReading::Ppm(0.62)] {
+            data.set_voc(voc);
+            assert_eq!(HibouAir::from_hex(&data.to_hex()).unwrap().get_voc_reading(), Some(voc));
+        }
+    }
+
+    #[test]
+    fn test_beacon_from_hex() {
+        let full = HibouAirBeacon::from_hex("0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002").unwrap();
+        assert!(matches!(full, HibouAirBeacon::Full(data) if data.get_co2() == 448));
+        assert_eq!(full.get_id(), 0x22005A);
+
+        // Beacons other than the full one only have their header read, whatever follows it.
+        assert_eq!(HibouAirBeacon::from_hex("02010608FF5B07040422005A"), Ok(HibouAirBeacon::Unknown(0x04, 0x22005A)));
+        assert_eq!(
+            HibouAirBeacon::from_hex("0201060CFF5B07060422005A57FFFF26"),
+            Ok(HibouAirBeacon::Unknown(0x06, 0x22005A))
+        );
+        assert_eq!(HibouAirBeacon::from_hex("02010608FF5B07090422005A"), Ok(HibouAirBeacon::Unknown(0x09, 0x22005A)));
+        assert!(HibouAirBeacon::from_hex("0201060CFF5B07050422005ABA27C600").is_err());
+        assert!(HibouAirBeacon::from_hex("02010605FF5B070504").is_err());
+        assert!(HibouAirBeacon::from_hex("0201060BFF4C000215").is_err());
+    }
+
+    #[test]
+    fn test_voc_readings() {
+        // The CO2 advert from above with each VOC type; its VOC value is 0x003E = 62.
+        let with_type = |voc_type: &str| {
+            HibouAir::from_hex(&format!("0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C0{voc_type}")).unwrap()
+        };
+
+        let ppm = with_type("02").get_voc_reading().unwrap();
+        assert_eq!(ppm, VocReading::Ppm(0.62));
+        assert_eq!(ppm.get_view(), "0.62 ppm");
+        assert_eq!(ppm.get_iaq_label(), None);
+
+        let iaq = with_type("03").get_voc_reading().unwrap();
+        assert_eq!(iaq, VocReading::Iaq(62));
+        assert_eq!(iaq.get_view(), "62 IAQ");
+        assert_eq!(iaq.get_iaq_label(), Some("Good"));
+        assert_eq!(VocReading::Iaq(180).get_iaq_label(), Some("Moderately polluted"));
+        assert_eq!(VocReading::Iaq(420).get_iaq_label(), Some("Extremely polluted"));
+
+        let resistance = with_type("01");
+        assert_eq!(resistance.get_voc_reading(), Some(VocReading::Resistance(62)));
+        assert_eq!(resistance.get_voc_view(), "62 raw");
+        assert_eq!(resistance.get_voc_unit(), "raw");
+        assert_eq!(
+            serde_json::to_string(&resistance.get_voc_reading()).unwrap(),
+            r#"{"type":"resistance_raw","value":62}"#
+        );
+
+        assert_eq!(with_type("00").get_voc_reading(), Some(VocReading::Old(62)));
+        assert_eq!(with_type("00").get_voc_view(), "62 raw");
+        assert_eq!(with_type("07").get_voc_reading(), None);
+        assert_eq!(with_type("07").get_voc(), 0.0);
+        // A zero from older firmware is a reading like any other.
+        let zero = HibouAir::from_hex("0201061BFF5B07050422005A0000BA27C6001701000000000000000001C000").unwrap();
+        assert_eq!(zero.get_voc_reading(), Some(VocReading::Old(0)));
+        // A board type without a VOC sensor has none, whatever the VOC bytes hold.
+        let no_voc = HibouAir::from_hex("0201061BFF5B07050022005A0000BA27C60017013E0000000000000001C002").unwrap();
+        assert_eq!(no_voc.get_voc_reading(), None);
+        assert_eq!(no_voc.get_voc_view(), "");
+    }
+
+    #[test]
+    fn test_measurements() {
+        assert!(HibouAirType::PmSensor.get_measurements().contains(&Measurement::Pm2_5));
+        assert!(!HibouAirType::Co2Sensor.get_measurements().contains(&Measurement::Pm2_5));
+        assert!(HibouAirType::Unknown.get_measurements().is_empty());
+        assert_eq!(HibouAirType::Matrix.get_measurements().len(), 9, "Unconfirmed types show every value");
+        assert!(!HibouAirType::Co2Noise.has_known_measurements());
+        assert_eq!(Measurement::Co2.format_value(448.0), "448 ppm");
+        assert_eq!(Measurement::Temp.format_value(19.84), "19.8 °C");
+        assert_eq!(Measurement::Pm2_5.format_value(3.0), "3.0 μg/m³");
+    }
+
     #[cfg(feature = "using_ble")]
     #[test]
     fn test_hibouair_from_ble() {
@@ -0,0 +1,141 @@
+// Longest line we accept from the dongle. Verbose scan results are a few hundred bytes,
+// anything past this is noise and is dropped up to the next line end.
+pub const MAX_LINE_LEN: usize = 1024;
+
+// Splits raw serial bytes into text lines. Lines end at CR or LF, so a junk byte never
+// swallows more than the line it landed in. Invalid UTF-8 and control characters are
+// dropped from a line instead of failing it, and overlong lines are skipped entirely.
+pub struct LineFramer {
+    line: Vec<u8>,
+    max_len: usize,
+    overflow: bool,   // skipping the rest of an overlong line
+    discarded: u64,   // bytes thrown away since the framer was created
+}
+
+impl LineFramer {
+    pub fn new() -> Self {
+        LineFramer::with_max_len(MAX_LINE_LEN)
+    }
+
+    pub fn with_max_len(max_len: usize) -> Self {
+        LineFramer {
+            line: Vec::new(),
+            max_len,
+            overflow: false,
+            discarded: 0,
+        }
+    }
+
+    pub fn get_discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    // Frame `input`, appending every completed non-empty line to `lines`.
+    pub fn feed(&mut self, input: &[u8], lines: &mut Vec<String>) {
+        for &byte in input {
+            match byte {
+                b'\r' | b'\n' => {
+                    if self.overflow {
+                        self.overflow = false;
+                    } else if let Some(line) = self.take_line() {
+                        lines.push(line);
+                    }
+                }
+                _ if self.overflow => self.discarded += 1,
+                _ if self.line.len() >= self.max_len => {
+                    self.discarded += self.line.len() as u64 + 1;
+                    self.line.clear();
+                    self.overflow = true;
+                }
+                _ => self.line.push(byte),
+            }
+        }
+    }
+
+    // Return the unterminated line left over when the stream ends.
+    pub fn finish(&mut self) -> Option<String> {
+        if self.overflow {
+            self.overflow = false;
+            return None;
+        }
+        self.take_line()
+    }
+
+    fn take_line(&mut self) -> Option<String> {
+        let mut text = String::with_capacity(self.line.len());
+        for chunk in self.line.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                if c.is_control() && c != '\t' {
+                    self.discarded += c.len_utf8() as u64;
+                } else {
+                    text.push(c);
+                }
+            }
+            self.discarded += chunk.invalid().len() as u64;
+        }
+        self.line.clear();
+
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            None
+        } else {
+            Some(trimmed.to_string())
+        }
+    }
+}
+
+impl Default for LineFramer {
+    fn default() -> Self {
+        LineFramer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(framer: &mut LineFramer, input: &[u8]) -> Vec<String> {
+        let mut lines = Vec::new();
+        framer.feed(input, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let mut framer = LineFramer::new();
+        assert_eq!(frame(&mut framer, b"OK\r\nERROR\n\r\nREADY\r"), vec!["OK", "ERROR", "READY"]);
+        assert_eq!(framer.get_discarded(), 0);
+    }
+
+    #[test]
+    fn test_partial_lines() {
+        let mut framer = LineFramer::new();
+        assert!(frame(&mut framer, b"{\"SE\":3,").is_empty());
+        assert_eq!(frame(&mut framer, b"\"action\":\"scan completed\"}\r\n"), vec![
+            "{\"SE\":3,\"action\":\"scan completed\"}",
+        ]);
+        assert!(frame(&mut framer, b"SCAN COMP").is_empty());
+        assert_eq!(framer.finish().as_deref(), Some("SCAN COMP"));
+        assert_eq!(framer.finish(), None);
+    }
+
+    #[test]
+    fn test_invalid_utf8_and_control_bytes() {
+        let mut framer = LineFramer::new();
+        let lines = frame(&mut framer, b"\xff\xfe{\"E\":1,\x00\"nol\":3}\r\n\x80\x1b\r\nOK\r\n");
+        assert_eq!(lines, vec!["{\"E\":1,\"nol\":3}", "OK"]);
+        assert_eq!(framer.get_discarded(), 5);
+    }
+
+    #[test]
+    fn test_overlong_line_skipped() {
+        let mut framer = LineFramer::with_max_len(8);
+        let lines = frame(&mut framer, b"0123456789ABCDEF\r\nOK\r\n");
+        assert_eq!(lines, vec!["OK"]);
+        assert_eq!(framer.get_discarded(), 16);
+
+        assert!(frame(&mut framer, b"0123456789").is_empty());
+        assert_eq!(framer.finish(), None);
+        assert_eq!(frame(&mut framer, b"\nOK\n"), vec!["OK"]);
+    }
+}
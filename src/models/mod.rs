@@ -1,3 +1,15 @@
+pub mod autoexec;
 pub mod bleuio;
+pub mod bleuio_client;
+#[cfg(all(test, target_os = "linux"))]
+pub mod bleuio_emulator;
+pub mod capture;
+pub mod config;
+pub mod gatt;
 pub mod hibouair;
-pub mod sensor_data;
\ No newline at end of file
+pub mod line_framer;
+pub mod sensor_data;
+pub mod sensor_reading;
+pub mod session;
+pub mod shutdown;
+pub mod transport;
\ No newline at end of file
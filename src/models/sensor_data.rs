@@ -1,4 +1,7 @@
-use crate::models::hibouair::HibouAir;
+use chrono::{DateTime, Local};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::models::hibouair::{HibouAir, HibouAirBeacon, Measurement};
 
 pub enum SensorType {
     HibouAir,
@@ -14,4 +17,300 @@ pub struct Sensor {
     pub location: String,
     pub sensor_type: SensorType,
     pub data: SensorData,
-}
\ No newline at end of file
+}
+
+// Weight of the newest RSSI sample in the smoothed value (exponential moving average).
+const RSSI_SMOOTHING: f64 = 0.3;
+
+// One dongle's latest sighting of a sensor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sighting {
+    pub rssi: Option<i32>,              // signal strength in dBm, if the dongle reports it
+    pub smoothed_rssi: Option<f64>,     // moving average of rssi, evens out single weak adverts
+    pub last_seen: DateTime<Local>,
+}
+
+impl Sighting {
+    fn update(&mut self, rssi: Option<i32>, now: DateTime<Local>) {
+        self.smoothed_rssi = match (self.smoothed_rssi, rssi) {
+            (Some(avg), Some(rssi)) => Some(avg + RSSI_SMOOTHING * (rssi as f64 - avg)),
+            (None, Some(rssi)) => Some(rssi as f64),
+            (avg, None) => avg,
+        };
+        self.rssi = rssi;
+        self.last_seen = now;
+    }
+}
+
+// Return 0-4 signal strength bars for an RSSI value in dBm.
+pub fn signal_bars(rssi: f64) -> u8 {
+    match rssi {
+        r if r >= -60.0 => 4,
+        r if r >= -70.0 => 3,
+        r if r >= -80.0 => 2,
+        r if r >= -90.0 => 1,
+        _ => 0,
+    }
+}
+
+// A sensor as shown on the dashboard: its latest reading, whichever dongle delivered it,
+// and every dongle that has heard it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedSensor {
+    pub reading: HibouAir,
+    pub addr: String,                           // advertising MAC address
+    pub last_seen: DateTime<Local>,
+    pub heard_by: BTreeMap<String, Sighting>,   // dongle name → latest sighting
+}
+
+// A board heard only through beacons this app doesn't decode, until its first full beacon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndecodedSighting {
+    pub beacon_nr: u8,                          // the latest beacon number heard
+    pub addr: String,
+    pub last_seen: DateTime<Local>,
+}
+
+impl TrackedSensor {
+    pub fn new(dongle: &str, reading: HibouAir, addr: &str, rssi: Option<i32>, now: DateTime<Local>) -> Self {
+        let mut sensor = TrackedSensor { reading, addr: addr.to_string(), last_seen: now, heard_by: BTreeMap::new() };
+        sensor.update(dongle, reading, addr, rssi, now);
+        sensor
+    }
+
+    // Store a new reading heard by `dongle`.
+    pub fn update(&mut self, dongle: &str, reading: HibouAir, addr: &str, rssi: Option<i32>, now: DateTime<Local>) {
+        self.reading = reading;
+        self.sighted(dongle, addr, rssi, now);
+    }
+
+    // Note that `dongle` heard the sensor.
+    fn sighted(&mut self, dongle: &str, addr: &str, rssi: Option<i32>, now: DateTime<Local>) {
+        self.addr = addr.to_string();
+        self.last_seen = now;
+        self.heard_by
+            .entry(dongle.to_string())
+            .or_insert(Sighting { rssi: None, smoothed_rssi: None, last_seen: now })
+            .update(rssi, now);
+    }
+
+    // Return the dongle with the strongest (smoothed) signal, preferring dongles that report RSSI.
+    pub fn get_best_dongle(&self) -> Option<(&String, &Sighting)> {
+        self.heard_by
+            .iter()
+            .max_by(|(_, a), (_, b)| {
+                let a = a.smoothed_rssi.unwrap_or(f64::MIN);
+                let b = b.smoothed_rssi.unwrap_or(f64::MIN);
+                a.total_cmp(&b)
+            })
+    }
+
+    // Return the smoothed RSSI of the dongle hearing this sensor best.
+    pub fn get_rssi(&self) -> Option<f64> {
+        self.get_best_dongle().and_then(|(_, s)| s.smoothed_rssi)
+    }
+
+    // Return a measurement in its unit, None if the board type doesn't report it.
+    pub fn get_value(&self, measurement: Measurement) -> Option<f64> {
+        measurement_value(&self.reading, measurement)
+    }
+
+    // Return a measurement with its unit, None as for get_value.
+    pub fn get_display(&self, measurement: Measurement) -> Option<String> {
+        match measurement {
+            Measurement::Voc => self.get_value(measurement).and(self.reading.get_voc_reading()).map(|v| v.get_view()),
+            _ => self.get_value(measurement).map(|v| measurement.format_value(v)),
+        }
+    }
+
+    // Return a short description of the dongles hearing this sensor, e.g. "A (-61 dBm), B".
+    pub fn get_heard_by_string(&self) -> String {
+        self.heard_by
+            .iter()
+            .map(|(name, s)| match s.rssi {
+                Some(rssi) => format!("{name} ({rssi} dBm)"),
+                None => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Return a measurement of a reading, None if the board type doesn't report it. For a board
+// type whose values aren't known a zero is taken as a field the board leaves empty, since the
+// two can't be told apart.
+pub fn measurement_value(reading: &HibouAir, measurement: Measurement) -> Option<f64> {
+    let board_type = reading.get_board_type();
+    if !board_type.get_measurements().contains(&measurement) {
+        return None;
+    }
+    let value = match measurement {
+        Measurement::Co2 => Some(reading.get_co2() as f64),
+        Measurement::Voc => reading.get_voc_reading().map(|v| v.get_value()),
+        Measurement::Pm1_0 => Some(reading.get_pm1_0()),
+        Measurement::Pm2_5 => Some(reading.get_pm2_5()),
+        Measurement::Pm10 => Some(reading.get_pm10()),
+        Measurement::Temp => Some(reading.get_temp()),
+        Measurement::Hum => Some(reading.get_hum()),
+        Measurement::Bar => Some(reading.get_bar()),
+        Measurement::Light => Some(reading.get_als() as f64),
+    };
+    value.filter(|v| board_type.has_known_measurements() || *v != 0.0)
+}
+
+// Merge a reading from one of several dongles into the shared sensor map.
+pub fn merge_reading(
+    sensors: &mut HashMap<u32, TrackedSensor>,
+    dongle: &str,
+    reading: HibouAir,
+    addr: &str,
+    rssi: Option<i32>,
+    now: DateTime<Local>,
+) {
+    sensors
+        .entry(reading.get_id())
+        .and_modify(|s| s.update(dongle, reading, addr, rssi, now))
+        .or_insert_with(|| TrackedSensor::new(dongle, reading, addr, rssi, now));
+}
+
+// Merge any HibouAir beacon into the shared sensor map. A beacon that isn't decoded still
+// counts as a sighting of a known sensor; a board heard only that way so far goes into
+// `undecoded` until its first full beacon. Return false if the sensor map didn't change.
+pub fn merge_beacon(
+    sensors: &mut HashMap<u32, TrackedSensor>,
+    undecoded: &mut BTreeMap<u32, UndecodedSighting>,
+    dongle: &str,
+    beacon: HibouAirBeacon,
+    addr: &str,
+    rssi: Option<i32>,
+    now: DateTime<Local>,
+) -> bool {
+    match beacon {
+        HibouAirBeacon::Full(reading) => {
+            undecoded.remove(&reading.get_id());
+            merge_reading(sensors, dongle, reading, addr, rssi, now);
+            true
+        }
+        HibouAirBeacon::Unknown(beacon_nr, id) => match sensors.get_mut(&id) {
+            Some(sensor) => {
+                sensor.sighted(dongle, addr, rssi, now);
+                true
+            }
+            None => {
+                undecoded.insert(id, UndecodedSighting { beacon_nr, addr: addr.to_string(), last_seen: now });
+                false
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CO2_ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+    const ADDR: &str = "F5:50:35:CF:B1:ED";
+
+    #[test]
+    fn test_merge_reading_from_two_dongles() {
+        let reading = HibouAir::from_hex(CO2_ADVERT).unwrap();
+        let t0 = Local::now();
+        let t1 = t0 + chrono::Duration::seconds(3);
+        let mut sensors = HashMap::new();
+
+        merge_reading(&mut sensors, "dongle-a", reading, ADDR, Some(-80), t0);
+        merge_reading(&mut sensors, "dongle-b", reading, ADDR, Some(-55), t1);
+        merge_reading(&mut sensors, "dongle-a", reading, ADDR, Some(-78), t1);
+
+        assert_eq!(sensors.len(), 1);
+        let sensor = &sensors[&reading.get_id()];
+        assert_eq!(sensor.last_seen, t1);
+        assert_eq!(sensor.heard_by.len(), 2);
+        assert_eq!(sensor.heard_by["dongle-a"].rssi, Some(-78));
+        assert_eq!(sensor.get_best_dongle().map(|(name, _)| name.as_str()), Some("dongle-b"));
+        assert_eq!(sensor.get_heard_by_string(), "dongle-a (-78 dBm), dongle-b (-55 dBm)");
+    }
+
+    #[test]
+    fn test_best_dongle_without_rssi() {
+        let reading = HibouAir::from_hex(CO2_ADVERT).unwrap();
+        let now = Local::now();
+        let mut sensor = TrackedSensor::new("dongle-a", reading, ADDR, None, now);
+        sensor.update("dongle-b", reading, ADDR, Some(-90), now);
+        assert_eq!(sensor.get_best_dongle().map(|(name, _)| name.as_str()), Some("dongle-b"));
+        assert_eq!(sensor.get_heard_by_string(), "dongle-a, dongle-b (-90 dBm)");
+    }
+
+    #[test]
+    fn test_smoothed_rssi() {
+        let reading = HibouAir::from_hex(CO2_ADVERT).unwrap();
+        let now = Local::now();
+        let mut sensor = TrackedSensor::new("dongle-a", reading, ADDR, Some(-60), now);
+        assert_eq!(sensor.get_rssi(), Some(-60.0));
+
+        // A single weak advert only pulls the average part of the way down.
+        sensor.update("dongle-a", reading, ADDR, Some(-90), now);
+        assert_eq!(sensor.heard_by["dongle-a"].rssi, Some(-90));
+        assert!((sensor.get_rssi().unwrap() - -69.0).abs() < 1e-9);
+
+        // Adverts without RSSI keep the average.
+        sensor.update("dongle-a", reading, ADDR, None, now);
+        assert!((sensor.get_rssi().unwrap() - -69.0).abs() < 1e-9);
+        assert_eq!(sensor.addr, ADDR);
+    }
+
+    #[test]
+    fn test_merge_undecoded_beacons() {
+        let other = HibouAirBeacon::from_hex("02010608FF5B07040422005A").unwrap();
+        let full = HibouAirBeacon::from_hex(CO2_ADVERT).unwrap();
+        let t0 = Local::now();
+        let t1 = t0 + chrono::Duration::seconds(3);
+        let mut sensors = HashMap::new();
+        let mut undecoded = BTreeMap::new();
+
+        // Before the first full beacon the board is only noted as heard.
+        assert!(!merge_beacon(&mut sensors, &mut undecoded, "dongle-a", other, ADDR, Some(-70), t0));
+        assert!(sensors.is_empty());
+        assert_eq!(undecoded[&0x22005A], UndecodedSighting { beacon_nr: 0x04, addr: ADDR.to_string(), last_seen: t0 });
+
+        assert!(merge_beacon(&mut sensors, &mut undecoded, "dongle-a", full, ADDR, Some(-70), t0));
+        assert_eq!(sensors[&0x22005A].reading.get_co2(), 448);
+        assert!(undecoded.is_empty());
+
+        // Afterwards it keeps the sensor's sightings current, but not its values.
+        assert!(merge_beacon(&mut sensors, &mut undecoded, "dongle-b", other, ADDR, Some(-60), t1));
+        let sensor = &sensors[&0x22005A];
+        assert_eq!(sensor.reading.get_co2(), 448);
+        assert_eq!(sensor.last_seen, t1);
+        assert_eq!(sensor.heard_by["dongle-b"].rssi, Some(-60));
+        assert!(undecoded.is_empty());
+    }
+
+    #[test]
+    fn test_values_by_board_type() {
+        let co2 = HibouAir::from_hex(CO2_ADVERT).unwrap();
+        let now = Local::now();
+        let sensor = TrackedSensor::new("dongle-a", co2, ADDR, None, now);
+        assert_eq!(sensor.get_value(Measurement::Co2), Some(448.0));
+        assert_eq!(sensor.get_value(Measurement::Temp), Some(19.8));
+        assert_eq!(sensor.get_value(Measurement::Pm2_5), None, "A CO2 sensor has no particle sensor");
+        assert_eq!(sensor.get_display(Measurement::Voc).as_deref(), Some("0.62 ppm"));
+
+        // The same advert from a PIR sensor, whose values aren't known, shows the ones it filled in.
+        let pir = HibouAir::from_hex("0201061BFF5B07050822005A0000BA27C60017013E0000000000000001C002").unwrap();
+        let sensor = TrackedSensor::new("dongle-a", pir, ADDR, None, now);
+        assert_eq!(sensor.get_value(Measurement::Co2), Some(448.0));
+        assert_eq!(sensor.get_value(Measurement::Temp), Some(19.8));
+        assert_eq!(sensor.get_value(Measurement::Pm2_5), None, "A zero from a PIR sensor isn't shown");
+        assert_eq!(sensor.get_value(Measurement::Light), None);
+    }
+
+    #[test]
+    fn test_signal_bars() {
+        assert_eq!(signal_bars(-45.0), 4);
+        assert_eq!(signal_bars(-65.0), 3);
+        assert_eq!(signal_bars(-80.0), 2);
+        assert_eq!(signal_bars(-89.5), 1);
+        assert_eq!(signal_bars(-100.0), 0);
+    }
+}
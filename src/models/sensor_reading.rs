@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::models::hibouair::{HibouAir, HibouAirType, Measurement, VocReading};
+use crate::models::sensor_data::{measurement_value, TrackedSensor};
+
+// Version of the SensorReading schema. Bump it when a field is renamed, removed or changes
+// meaning; adding an optional field keeps it, as readers take a missing field as null.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/* A decoded reading, the form readings leave the app in. As JSON:
+    {"schema":1,"board_id":"22005A","board_type":"co2_sensor","measurements_known":true,"beacon_nr":5,
+     "timestamp":"2026-10-17T08:30:00Z","rssi":-61,"co2_ppm":448,"voc":{"type":"ppm","value":0.62},
+     "pm1_0_ugm3":null,...,"temperature_c":19.8,"humidity_rh":27.9,"pressure_hpa":1017.0,...}
+   Measurements are in the unit their name ends in, null when the board type doesn't have them.
+   When measurements_known is false, which values the board type has isn't known: zeros are
+   left out as null, and a value that is set may still be one the board doesn't measure.
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SensorReading {
+    pub schema: u32,
+    pub board_id: String,               // six hex digits, as printed on the sensor
+    pub board_type: HibouAirType,
+    #[serde(default)]
+    pub measurements_known: bool,       // false when the values may hold fields the board leaves unused
+    pub beacon_nr: u8,
+    pub timestamp: DateTime<Utc>,
+    pub rssi: Option<i32>,              // dBm
+    pub co2_ppm: Option<u16>,
+    pub voc: Option<VocReading>,        // unit depends on the VOC type, none for the raw types
+    pub pm1_0_ugm3: Option<f64>,
+    pub pm2_5_ugm3: Option<f64>,
+    pub pm10_ugm3: Option<f64>,
+    pub temperature_c: Option<f64>,
+    pub humidity_rh: Option<f64>,
+    pub pressure_hpa: Option<f64>,
+    pub light_lux: Option<u16>,
+}
+
+impl SensorReading {
+    // Decode a full beacon.
+    pub fn new(reading: &HibouAir, timestamp: DateTime<Utc>, rssi: Option<i32>) -> Self {
+        let value = |measurement| measurement_value(reading, measurement);
+        SensorReading {
+            schema: SCHEMA_VERSION,
+            board_id: reading.get_board_id_string(),
+            board_type: reading.get_board_type(),
+            measurements_known: reading.get_board_type().has_known_measurements(),
+            beacon_nr: reading.get_beacon_nr(),
+            timestamp,
+            rssi,
+            co2_ppm: value(Measurement::Co2).map(|v| v as u16),
+            voc: value(Measurement::Voc).and(reading.get_voc_reading()),
+            pm1_0_ugm3: value(Measurement::Pm1_0),
+            pm2_5_ugm3: value(Measurement::Pm2_5),
+            pm10_ugm3: value(Measurement::Pm10),
+            temperature_c: value(Measurement::Temp),
+            humidity_rh: value(Measurement::Hum),
+            pressure_hpa: value(Measurement::Bar),
+            light_lux: value(Measurement::Light).map(|v| v as u16),
+        }
+    }
+
+    // The latest state of a tracked sensor, with the RSSI of the dongle hearing it best.
+    pub fn from_tracked(sensor: &TrackedSensor) -> Self {
+        let rssi = sensor.get_best_dongle().and_then(|(_, s)| s.rssi);
+        Self::new(&sensor.reading, sensor.last_seen.to_utc(), rssi)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    // Parse a reading, refusing one written with a newer schema than this app knows.
+    pub fn from_json(json: &str) -> std::result::Result<Self, String> {
+        let reading: SensorReading = serde_json::from_str(json).map_err(|e| format!("Invalid sensor reading: {e}"))?;
+        if reading.schema > SCHEMA_VERSION {
+            return Err(format!("Sensor reading schema {} is newer than {SCHEMA_VERSION}", reading.schema));
+        }
+        Ok(reading)
+    }
+}
+
+// Writes decoded readings to a file, one JSON object per line. Clones write to the same
+// file, so every dongle's readings end up in one file.
+#[derive(Clone)]
+pub struct ReadingsWriter {
+    out: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl ReadingsWriter {
+    // Create (or truncate) a readings file.
+    pub fn create<P: AsRef<Path>>(path: P) -> std::io::Result<Self> {
+        Ok(ReadingsWriter { out: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))) })
+    }
+
+    // Append a reading. Flushed right away so a crash doesn't lose the tail.
+    pub fn write(&mut self, reading: &SensorReading) -> std::io::Result<()> {
+        let mut out = self.out.lock().map_err(|_| std::io::Error::other("readings writer poisoned"))?;
+        writeln!(out, "{}", reading.to_json())?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::sensor_data::merge_reading;
+    use std::collections::HashMap;
+
+    const CO2_ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+
+    fn timestamp() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2026-10-17T08:30:00Z").unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_sensor_reading_json() {
+        let reading = SensorReading::new(&HibouAir::from_hex(CO2_ADVERT).unwrap(), timestamp(), Some(-61));
+        assert_eq!(reading.co2_ppm, Some(448));
+        assert_eq!(reading.pm2_5_ugm3, None, "A CO2 sensor has no particle sensor");
+        assert_eq!(
+            reading.to_json(),
+            concat!(
+                r#"{"schema":1,"board_id":"22005A","board_type":"co2_sensor","measurements_known":true,"beacon_nr":5,"#,
+                r#""timestamp":"2026-10-17T08:30:00Z","rssi":-61,"co2_ppm":448,"voc":{"type":"ppm","value":0.62},"#,
+                r#""pm1_0_ugm3":null,"pm2_5_ugm3":null,"pm10_ugm3":null,"temperature_c":19.8,"humidity_rh":27.9,"#,
+                r#""pressure_hpa":1017.0,"light_lux":0}"#,
+            )
+        );
+        assert_eq!(SensorReading::from_json(&reading.to_json()), Ok(reading));
+    }
+
+    #[test]
+    fn test_sensor_reading_from_tracked() {
+        let full = HibouAir::from_hex("0201061BFF5B07050A22005A0000BA27C60017013E0000000000000001C003").unwrap();
+        let mut sensors = HashMap::new();
+        let now = timestamp().into();
+        merge_reading(&mut sensors, "dongle-a", full, "F5:50:35:CF:B1:ED", Some(-70), now);
+        merge_reading(&mut sensors, "dongle-b", full, "F5:50:35:CF:B1:ED", Some(-55), now);
+
+        let reading = SensorReading::from_tracked(&sensors[&0x22005A]);
+        assert_eq!(reading.board_type, HibouAirType::DuoMaster);
+        assert!(!reading.measurements_known);
+        assert_eq!(reading.timestamp, timestamp());
+        assert_eq!(reading.rssi, Some(-55));
+        assert_eq!(reading.voc, Some(VocReading::Iaq(62)));
+        assert_eq!(reading.co2_ppm, Some(448));
+        assert_eq!(reading.light_lux, None, "A zero from a duo may be a value it doesn't have");
+    }
+
+    #[test]
+    fn test_readings_writer() {
+        let path = std::env::temp_dir().join(format!("hibouair-readings-{}.jsonl", std::process::id()));
+        let reading = SensorReading::new(&HibouAir::from_hex(CO2_ADVERT).unwrap(), timestamp(), None);
+
+        let mut writer = ReadingsWriter::create(&path).unwrap();
+        writer.write(&reading).unwrap();
+        writer.clone().write(&reading).unwrap();
+        drop(writer);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let lines: Vec<_> = text.lines().map(SensorReading::from_json).collect();
+        assert_eq!(lines, vec![Ok(reading.clone()), Ok(reading)]);
+    }
+
+    #[test]
+    fn test_sensor_reading_schema() {
+        // Fields added after a reading was written are read as null.
+        let old = r#"{"schema":1,"board_id":"22005A","board_type":"pm_sensor","beacon_nr":5,"timestamp":"2026-10-17T08:30:00Z","rssi":null,"pm2_5_ugm3":3.4}"#;
+        let reading = SensorReading::from_json(old).unwrap();
+        assert_eq!(reading.board_type, HibouAirType::PmSensor);
+        assert_eq!(reading.pm2_5_ugm3, Some(3.4));
+        assert_eq!(reading.co2_ppm, None);
+        assert!(!reading.measurements_known, "Readings without the marker aren't vouched for");
+
+        // A board type this app doesn't know yet doesn't make the reading unreadable.
+        let new_type = old.replace("pm_sensor", "co2_pm_sensor");
+        assert_eq!(SensorReading::from_json(&new_type).map(|r| r.board_type), Ok(HibouAirType::Unknown));
+
+        let newer = old.replace(r#""schema":1"#, r#""schema":2"#);
+        assert!(SensorReading::from_json(&newer).is_err());
+        assert!(SensorReading::from_json(r#"{"schema":1}"#).is_err());
+    }
+}
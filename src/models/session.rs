@@ -0,0 +1,858 @@
+use chrono::{DateTime, Local};
+use futures_util::stream::FusedStream;
+use futures_util::{FutureExt, Stream, StreamExt};
+use futures_channel::mpsc::UnboundedReceiver;
+use std::future::Future;
+use tokio::time::{sleep_until, timeout, Duration, Instant};
+
+use crate::models::bleuio::*;
+use crate::models::bleuio_client::{BleuIOClient, BleuIOSource, DongleTarget};
+use crate::models::capture::CaptureWriter;
+use crate::models::gatt::*;
+
+const BACKOFF_MIN: Duration = Duration::from_secs(1);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+// How long to wait for the dongle to confirm a stopped scan when shutting down.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
+// How long a sensor gets to accept a connection, and to confirm a disconnect.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+// Upper bound on characteristics read from one sensor.
+const MAX_READS: usize = 32;
+
+// Exponential backoff between reconnect attempts.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+}
+
+impl Backoff {
+    pub fn new(min: Duration, max: Duration) -> Self {
+        Backoff { min, max, current: min }
+    }
+
+    // Return the delay before the next attempt and double it for the one after.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.current;
+        self.current = (self.current * 2).min(self.max);
+        delay
+    }
+
+    // Start over from the shortest delay, e.g. after a successful connection.
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(BACKOFF_MIN, BACKOFF_MAX)
+    }
+}
+
+// Why the watchdog restarted a scan.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RestartReason {
+    ScanEnded,              // the dongle sent SE
+    Silence(Duration),      // no scan result for this long
+}
+
+// One scan restart, kept for diagnostics.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanRestart {
+    pub reason: RestartReason,
+    pub at: DateTime<Local>,
+}
+
+impl ScanRestart {
+    pub fn get_description(&self) -> String {
+        let reason = match &self.reason {
+            RestartReason::ScanEnded => "scan ended".to_string(),
+            RestartReason::Silence(window) => format!("no results for {} s", window.as_secs()),
+        };
+        format!("{} {reason}", self.at.format("%Y-%m-%d %H:%M:%S"))
+    }
+}
+
+// What the UI asks a session to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionCommand {
+    Send(BleuIOCommand),            // forward a command to the dongle
+    ReadSensor(BleAddress),         // connect to a sensor, read its characteristics, then scan again
+    ReadAutoexec,                   // report the dongle's autoexec list
+    WriteAutoexec(Vec<String>),     // replace the autoexec list; an empty list clears it
+}
+
+// Progress reported by a running session.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    Connecting(String),             // about to open the named source
+    Initializing(String),           // port open, running the init handshake
+    Connected(String),              // handshake done, scanning
+    DeviceInfo(BleuIODeviceInfo),   // what ATI and AT+GETMAC reported after connecting
+    ScanResult(BleuIOResponse),     // S, SF, ST or SE line from the dongle
+    CommandFailed(String),          // a command from the UI failed, with command and reason
+    ScanRestarted(ScanRestart),     // the watchdog reissued the scan command
+    Gatt(GattEvent),                // progress of a ReadSensor command
+    Autoexec(Vec<String>),          // the dongle's autoexec list, after reading or writing it
+    Disconnected(String),           // connection lost or could not be made, with reason
+}
+
+// Connection state of one dongle, as shown in the UI.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionState {
+    Waiting,            // no dongle found yet or waiting before the next attempt
+    Opening,
+    Initializing,
+    Scanning,
+    ReadingSensor(String),  // scan paused while connected to the sensor with this address
+    Error(String),
+}
+
+impl ConnectionState {
+    // Return the state after `event`, or None if the event doesn't change it.
+    pub fn from_event(event: &SessionEvent) -> Option<Self> {
+        match event {
+            SessionEvent::Connecting(_) => Some(ConnectionState::Opening),
+            SessionEvent::Initializing(_) => Some(ConnectionState::Initializing),
+            SessionEvent::Connected(_) => Some(ConnectionState::Scanning),
+            SessionEvent::Disconnected(reason) => Some(ConnectionState::Error(reason.clone())),
+            SessionEvent::Gatt(GattEvent::Connecting(addr)) => Some(ConnectionState::ReadingSensor(addr.clone())),
+            SessionEvent::Gatt(GattEvent::Disconnected(..) | GattEvent::Failed(..)) => Some(ConnectionState::Scanning),
+            SessionEvent::Gatt(_)
+            | SessionEvent::Autoexec(_)
+            | SessionEvent::ScanResult(_)
+            | SessionEvent::CommandFailed(_)
+            | SessionEvent::DeviceInfo(_)
+            | SessionEvent::ScanRestarted(_) => None,
+        }
+    }
+
+    // Return a short description for the status line.
+    pub fn get_label(&self) -> String {
+        match self {
+            ConnectionState::Waiting => "Waiting for dongle".to_string(),
+            ConnectionState::Opening => "Opening port".to_string(),
+            ConnectionState::Initializing => "Initializing".to_string(),
+            ConnectionState::Scanning => "Scanning".to_string(),
+            ConnectionState::ReadingSensor(addr) => format!("Reading {addr}"),
+            ConnectionState::Error(reason) => format!("Error: {reason}"),
+        }
+    }
+}
+
+// Why a connection ended.
+enum SessionEnd {
+    Stopped,            // the command stream closed, i.e. the UI is gone
+    Finished,           // a replay reached the end of its file
+    Lost(String),       // the dongle went away or failed; try again
+}
+
+// Keeps the connection to one dongle alive: finds and opens its port, runs the init
+// handshake, starts scanning, and reconnects with backoff whenever the port fails or
+// disappears. While connected a watchdog restarts the scan when the dongle ends it (SE)
+// or goes quiet for longer than the silence window.
+pub struct BleuIOSession {
+    target: DongleTarget,
+    backoff: Backoff,
+    capture: Option<CaptureWriter>,
+    scan: BleuIOCommand,
+    silence_window: Option<Duration>,
+    usb_variants: Vec<BleuIOVariant>,
+}
+
+impl BleuIOSession {
+    pub fn new(target: DongleTarget) -> Self {
+        BleuIOSession {
+            target,
+            backoff: Backoff::default(),
+            capture: None,
+            scan: BleuIOCommand::hibouair_scan(),
+            silence_window: None,
+            usb_variants: Vec::new(),
+        }
+    }
+
+    // Return the name of the dongle this session talks to.
+    pub fn get_name(&self) -> String {
+        self.target.get_name()
+    }
+
+    // Write every line received on this session to a capture file.
+    pub fn set_capture(&mut self, capture: CaptureWriter) {
+        self.capture = Some(capture);
+    }
+
+    // Set the command that starts scanning after each (re)connect.
+    pub fn set_scan_command(&mut self, scan: BleuIOCommand) {
+        self.scan = scan;
+    }
+
+    // Restart the scan when nothing has been heard for `window`. None turns the silence check off;
+    // a scan that ends with SE is always restarted.
+    pub fn set_silence_window(&mut self, window: Option<Duration>) {
+        self.silence_window = window;
+    }
+
+    // Recognise these VID/PID pairs as dongles too, when finding the dongle by serial number
+    // and when telling its model.
+    pub fn set_usb_variants(&mut self, variants: Vec<BleuIOVariant>) {
+        self.usb_variants = variants;
+    }
+
+    pub fn set_backoff(&mut self, backoff: Backoff) {
+        self.backoff = backoff;
+    }
+
+    // Run until `commands` ends. Commands are forwarded to the dongle while connected.
+    pub async fn run<S, F>(&mut self, commands: &mut S, on_event: F)
+    where
+        S: Stream<Item = SessionCommand> + Unpin,
+        F: FnMut(SessionEvent),
+    {
+        let variants = self.usb_variants.clone();
+        self.run_with(commands, on_event, |source| {
+            let source = source.clone();
+            let variants = variants.clone();
+            async move { BleuIOClient::connect(&source, &variants).await }
+        })
+        .await
+    }
+
+    // Same as `run`, with a custom way of turning a source into a client.
+    pub async fn run_with<S, F, C, Fut>(&mut self, commands: &mut S, mut on_event: F, mut connect: C)
+    where
+        S: Stream<Item = SessionCommand> + Unpin,
+        F: FnMut(SessionEvent),
+        C: FnMut(&BleuIOSource) -> Fut,
+        Fut: Future<Output = Result<BleuIOClient, BleuIOError>>,
+    {
+        loop {
+            let source = self.target.resolve(&self.usb_variants);
+            let end = match &source {
+                BleuIOSource::Serial(port_name) if port_name.is_empty() => {
+                    SessionEnd::Lost("No BleuIO dongle found".to_string())
+                }
+                _ => {
+                    on_event(SessionEvent::Connecting(source.to_string()));
+                    match connect(&source).await {
+                        Ok(client) => self.run_client(client, &source, commands, &mut on_event).await,
+                        Err(e) => SessionEnd::Lost(e.to_string()),
+                    }
+                }
+            };
+
+            match end {
+                SessionEnd::Stopped | SessionEnd::Finished => return,
+                SessionEnd::Lost(reason) => {
+                    on_event(SessionEvent::Disconnected(reason));
+                    // Keep draining commands while waiting so a closed UI stops us right away.
+                    // Commands sent while disconnected are dropped.
+                    let deadline = Instant::now() + self.backoff.next_delay();
+                    loop {
+                        tokio::select! {
+                            _ = sleep_until(deadline) => break,
+                            cmd = commands.next() => {
+                                if cmd.is_none() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn run_client<S, F>(
+        &mut self,
+        mut client: BleuIOClient,
+        source: &BleuIOSource,
+        commands: &mut S,
+        on_event: &mut F,
+    ) -> SessionEnd
+    where
+        S: Stream<Item = SessionCommand> + Unpin,
+        F: FnMut(SessionEvent),
+    {
+        if let Some(capture) = &self.capture {
+            client.set_capture(capture.clone());
+        }
+        let (Some(mut scan_results), Some(mut events)) = (client.take_scan_results(), client.take_events()) else {
+            return SessionEnd::Lost("Scan results already taken".to_string());
+        };
+
+        // Ctrl-C → ATE0 → ATV1 → AT+SHOWRSSI=1 → ATI → AT+GETMAC → AT+FINDSCANDATA or AT+SCANTARGET
+        if source.needs_handshake() {
+            on_event(SessionEvent::Initializing(source.to_string()));
+            if let Err(e) = client.init().await {
+                return SessionEnd::Lost(format!("Init failed: {e}"));
+            }
+            while let Some(Some(_)) = scan_results.next().now_or_never() {}
+            match client.query_info().await {
+                Ok(info) => on_event(SessionEvent::DeviceInfo(info)),
+                Err(e @ BleuIOError::Reset(_)) => return SessionEnd::Lost(e.to_string()),
+                Err(e) => on_event(SessionEvent::CommandFailed(format!("ATI: {e}"))),
+            }
+            if let Err(e) = client.start_scan(self.scan.clone()).await {
+                return SessionEnd::Lost(format!("Could not start scan: {e}"));
+            }
+        }
+        on_event(SessionEvent::Connected(source.to_string()));
+        self.backoff.reset();
+
+        let mut last_result = Instant::now();
+        // Set when the user stops the scan, so its SE isn't taken for a scan that ended by itself.
+        let mut scan_stopped = false;
+        loop {
+            let restart = tokio::select! {
+                scan = scan_results.next() => {
+                    match scan {
+                        Some(response) => {
+                            last_result = Instant::now();
+                            let ended = response.get_type() == BleuIOResponseType::ScanEndedResponse;
+                            on_event(SessionEvent::ScanResult(response));
+                            (ended && source.needs_handshake() && !scan_stopped).then_some(RestartReason::ScanEnded)
+                        }
+                        None if matches!(source, BleuIOSource::Network { .. }) => return SessionEnd::Lost("Connection closed".to_string()),
+                        None if source.needs_handshake() => return SessionEnd::Lost("Serial port closed".to_string()),
+                        None => return SessionEnd::Finished,
+                    }
+                }
+                cmd = commands.next() => {
+                    match cmd {
+                        Some(SessionCommand::Send(cmd)) => match client.execute(cmd.clone()).await {
+                            Ok(_) if cmd == BleuIOCommand::StopScan => scan_stopped = true,
+                            Ok(_) if cmd.starts_scan() => {
+                                scan_stopped = false;
+                                last_result = Instant::now();
+                            }
+                            Ok(_) => {}
+                            Err(e @ BleuIOError::Reset(_)) => return SessionEnd::Lost(e.to_string()),
+                            Err(e) => on_event(SessionEvent::CommandFailed(format!("{}: {e}", cmd.to_command_string()))),
+                        },
+                        Some(SessionCommand::ReadSensor(addr)) if source.needs_handshake() => {
+                            if let Err(e) = read_sensor(&client, &mut scan_results, &mut events, &addr, on_event).await {
+                                on_event(SessionEvent::Gatt(GattEvent::Failed(addr.mac, e.to_string())));
+                                return SessionEnd::Lost(e.to_string());
+                            }
+                            if !scan_stopped {
+                                if let Err(e) = client.start_scan(self.scan.clone()).await {
+                                    return SessionEnd::Lost(format!("Could not restart scan: {e}"));
+                                }
+                            }
+                            last_result = Instant::now();
+                        }
+                        Some(SessionCommand::ReadSensor(addr)) => {
+                            on_event(SessionEvent::Gatt(GattEvent::Failed(addr.mac, "Not available in a replay".to_string())));
+                        }
+                        Some(cmd @ (SessionCommand::ReadAutoexec | SessionCommand::WriteAutoexec(_))) if source.needs_handshake() => {
+                            let result = match &cmd {
+                                SessionCommand::WriteAutoexec(commands) => client.write_autoexec(commands).await,
+                                _ => client.read_autoexec().await,
+                            };
+                            match result {
+                                Ok(list) => on_event(SessionEvent::Autoexec(list)),
+                                Err(e @ BleuIOError::Reset(_)) => return SessionEnd::Lost(e.to_string()),
+                                Err(e) => {
+                                    on_event(SessionEvent::CommandFailed(format!("Autoexec: {e}")));
+                                    // A write that failed half way has already cleared the list
+                                    // and added some of it; show what the dongle has now.
+                                    if let SessionCommand::WriteAutoexec(_) = cmd {
+                                        if let Ok(list) = client.read_autoexec().await {
+                                            on_event(SessionEvent::Autoexec(list));
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        Some(SessionCommand::ReadAutoexec | SessionCommand::WriteAutoexec(_)) => {
+                            on_event(SessionEvent::CommandFailed("Autoexec: not available in a replay".to_string()));
+                        }
+                        None => {
+                            stop(client, &mut scan_results, source).await;
+                            return SessionEnd::Stopped;
+                        }
+                    }
+                    None
+                }
+                // Connection events only matter while reading a sensor; don't let them pile up.
+                _ = events.next(), if !events.is_terminated() => None,
+                window = watchdog(last_result, self.silence_window), if source.needs_handshake() && !scan_stopped => {
+                    Some(RestartReason::Silence(window))
+                }
+            };
+
+            if let Some(reason) = restart {
+                // A silent scan is still running as far as the dongle knows and would refuse a new one.
+                if let RestartReason::Silence(_) = reason {
+                    stop_scan(&client, &mut scan_results).await;
+                }
+                if let Err(e) = client.start_scan(self.scan.clone()).await {
+                    return SessionEnd::Lost(format!("Could not restart scan: {e}"));
+                }
+                last_result = Instant::now();
+                on_event(SessionEvent::ScanRestarted(ScanRestart { reason, at: Local::now() }));
+            }
+        }
+    }
+}
+
+// Leave the dongle idle for the next program: stop the scan, then flush the capture and
+// close the port.
+async fn stop(client: BleuIOClient, scan_results: &mut UnboundedReceiver<BleuIOResponse>, source: &BleuIOSource) {
+    if source.needs_handshake() {
+        stop_scan(&client, scan_results).await;
+    }
+    client.close().await;
+}
+
+// Stop the scan (Ctrl-C) and wait for the dongle to report its end.
+async fn stop_scan(client: &BleuIOClient, scan_results: &mut UnboundedReceiver<BleuIOResponse>) {
+    if client.send(BleuIOCommand::StopScan).await.is_ok() {
+        let scan_ended = async {
+            while let Some(response) = scan_results.next().await {
+                if response.get_type() == BleuIOResponseType::ScanEndedResponse {
+                    break;
+                }
+            }
+        };
+        timeout(STOP_TIMEOUT, scan_ended).await.ok();
+    }
+}
+
+// Connect to a sensor, read every readable characteristic and disconnect again, reporting
+// progress as Gatt events. The dongle can't connect while scanning, so the scan is stopped
+// first; the caller starts it again. Errors that end the connection to the dongle are returned
+// for the caller to report, anything else is reported here as GattEvent::Failed.
+async fn read_sensor<F>(
+    client: &BleuIOClient,
+    scan_results: &mut UnboundedReceiver<BleuIOResponse>,
+    events: &mut UnboundedReceiver<BleuIOEvent>,
+    addr: &BleAddress,
+    on_event: &mut F,
+) -> Result<(), BleuIOError>
+where
+    F: FnMut(SessionEvent),
+{
+    let name = addr.mac.clone();
+    if !client.get_capabilities().gatt_client {
+        on_event(SessionEvent::Gatt(GattEvent::Failed(name, "This dongle can't connect to sensors".to_string())));
+        return Ok(());
+    }
+    on_event(SessionEvent::Gatt(GattEvent::Connecting(name.clone())));
+    stop_scan(client, scan_results).await;
+    while let Some(Some(_)) = events.next().now_or_never() {}
+
+    if let Err(e) = client.execute(BleuIOCommand::AtGapConnect(addr.clone())).await {
+        if is_fatal(&e) {
+            return Err(e);
+        }
+        on_event(SessionEvent::Gatt(GattEvent::Failed(name, format!("Could not connect: {e}"))));
+        return Ok(());
+    }
+    match timeout(CONNECT_TIMEOUT, next_connection_event(events)).await {
+        Ok(Some(ConnectionEvent::Connected { .. })) => on_event(SessionEvent::Gatt(GattEvent::Connected(name.clone()))),
+        Ok(Some(ConnectionEvent::Disconnected { reason, .. })) => {
+            let reason = reason.map(|r| format!("Connection failed ({r})")).unwrap_or("Connection failed".to_string());
+            on_event(SessionEvent::Gatt(GattEvent::Failed(name, reason)));
+            return Ok(());
+        }
+        Ok(None) => return Err(BleuIOError::Closed),
+        Err(_) => {
+            client.send(BleuIOCommand::AtCancelConnect).await.ok();
+            on_event(SessionEvent::Gatt(GattEvent::Failed(name, "Sensor did not accept the connection".to_string())));
+            return Ok(());
+        }
+    }
+
+    let reason = match read_values(client, events, &name, on_event).await? {
+        Some(reason) => reason,
+        None => {
+            client.send(BleuIOCommand::AtGapDisconnect).await.ok();
+            match timeout(DISCONNECT_TIMEOUT, next_connection_event(events)).await {
+                Ok(Some(ConnectionEvent::Disconnected { reason: Some(reason), .. })) => reason,
+                _ => "disconnected".to_string(),
+            }
+        }
+    };
+    on_event(SessionEvent::Gatt(GattEvent::Disconnected(name, reason)));
+    Ok(())
+}
+
+// Read the readable characteristics of the connected sensor. Returns the reason if the sensor
+// dropped the connection before all were read.
+async fn read_values<F>(
+    client: &BleuIOClient,
+    events: &mut UnboundedReceiver<BleuIOEvent>,
+    name: &str,
+    on_event: &mut F,
+) -> Result<Option<String>, BleuIOError>
+where
+    F: FnMut(SessionEvent),
+{
+    let characteristics = match client.execute(BleuIOCommand::AtGetServices).await {
+        Ok(lines) => parse_characteristics(&lines),
+        Err(e) if is_fatal(&e) => return Err(e),
+        Err(e) => {
+            on_event(SessionEvent::Gatt(GattEvent::Failed(name.to_string(), format!("Could not list characteristics: {e}"))));
+            return Ok(None);
+        }
+    };
+    if characteristics.is_empty() {
+        on_event(SessionEvent::Gatt(GattEvent::Failed(name.to_string(), "AT+GETSERVICES listed no characteristics in a format this app knows".to_string())));
+        return Ok(None);
+    }
+
+    for characteristic in characteristics.into_iter().filter(|c| c.is_readable()).take(MAX_READS) {
+        while let Some(Some(event)) = events.next().now_or_never() {
+            if let Some(ConnectionEvent::Disconnected { reason, .. }) = event.get_connection_event() {
+                return Ok(Some(reason.unwrap_or("disconnected by sensor".to_string())));
+            }
+        }
+        // Plain send: some characteristics need pairing and fail, which shouldn't count
+        // towards resetting the dongle.
+        match client.send(BleuIOCommand::AtGattcRead(characteristic.handle)).await {
+            Ok(lines) => {
+                if let Some(value) = parse_read_value(&lines) {
+                    on_event(SessionEvent::Gatt(GattEvent::Value(name.to_string(), GattValue { characteristic, value })));
+                }
+            }
+            Err(e @ (BleuIOError::Io(_) | BleuIOError::Closed)) => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(None)
+}
+
+// Wait for the next connect or disconnect event. None if the port closed.
+async fn next_connection_event(events: &mut UnboundedReceiver<BleuIOEvent>) -> Option<ConnectionEvent> {
+    while let Some(event) = events.next().await {
+        if let Some(connection) = event.get_connection_event() {
+            return Some(connection);
+        }
+    }
+    None
+}
+
+// Return true for errors after which the dongle has to be reconnected.
+fn is_fatal(e: &BleuIOError) -> bool {
+    matches!(e, BleuIOError::Io(_) | BleuIOError::Closed | BleuIOError::Reset(_))
+}
+
+// Complete when `window` has passed since `last_result`; never when there is no window.
+async fn watchdog(last_result: Instant, window: Option<Duration>) -> Duration {
+    match window {
+        Some(window) => {
+            sleep_until(last_result + window).await;
+            window
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_connection_state_from_event() {
+        let name = "/dev/ttyACM0".to_string();
+        assert_eq!(ConnectionState::from_event(&SessionEvent::Connecting(name.clone())), Some(ConnectionState::Opening));
+        assert_eq!(ConnectionState::from_event(&SessionEvent::Initializing(name.clone())), Some(ConnectionState::Initializing));
+        assert_eq!(ConnectionState::from_event(&SessionEvent::Connected(name)), Some(ConnectionState::Scanning));
+        assert_eq!(
+            ConnectionState::from_event(&SessionEvent::Disconnected("Serial port closed".to_string())),
+            Some(ConnectionState::Error("Serial port closed".to_string()))
+        );
+        let scan = SessionEvent::ScanResult(BleuIOResponse::ScanEnded { idx: 1, action: String::new() });
+        assert_eq!(ConnectionState::from_event(&scan), None);
+        assert_eq!(ConnectionState::Error("gone".to_string()).get_label(), "Error: gone");
+    }
+
+    #[tokio::test]
+    async fn test_session_stops_when_commands_end() {
+        let mut session = BleuIOSession::new(DongleTarget::Port("/dev/does-not-exist".to_string()));
+        session.set_backoff(Backoff::new(Duration::from_millis(1), Duration::from_millis(4)));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut failures = 0;
+        session
+            .run(&mut rx, |event| {
+                if let SessionEvent::Disconnected(_) = event {
+                    failures += 1;
+                    if failures == 3 {
+                        tx.take();
+                    }
+                }
+            })
+            .await;
+        assert_eq!(failures, 3);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_session_reconnects_after_unplug() {
+        use crate::models::bleuio_emulator::*;
+
+        const ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let mut emulators = [
+            Some(BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap()),
+            Some(BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap()),
+        ];
+        let paths: Vec<String> = emulators.iter().flatten().map(|e| e.slave_path().to_string()).collect();
+
+        let mut session = BleuIOSession::new(DongleTarget::Port(paths[0].clone()));
+        session.set_backoff(Backoff::new(Duration::from_millis(10), Duration::from_millis(10)));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut attempts = 0;
+        let mut scans = 0;
+        let mut events = Vec::new();
+
+        session
+            .run_with(
+                &mut rx,
+                |event| {
+                    if let SessionEvent::ScanResult(_) = event {
+                        scans += 1;
+                        if scans == 1 {
+                            // First dongle is unplugged as soon as it has delivered a reading.
+                            emulators[0] = None;
+                        } else {
+                            tx.take();
+                        }
+                    }
+                    events.push(event);
+                },
+                |_source| {
+                    // The replugged dongle shows up under a new device path.
+                    let path = &paths[attempts.min(1)];
+                    attempts += 1;
+                    std::future::ready(BleuIOClient::open(path))
+                },
+            )
+            .await;
+
+        let connected = events.iter().filter(|e| matches!(e, SessionEvent::Connected(_))).count();
+        let disconnected = events.iter().filter(|e| matches!(e, SessionEvent::Disconnected(_))).count();
+        assert_eq!(connected, 2);
+        assert_eq!(disconnected, 1);
+        assert!(events.iter().any(|e| matches!(e, SessionEvent::DeviceInfo(info) if info.firmware == "2.7.5")));
+        // The init sequence turns on RSSI reporting.
+        assert!(events.iter().any(|e| matches!(e, SessionEvent::ScanResult(BleuIOResponse::ScanFindData { rssi: Some(-61), .. }))));
+    }
+
+    // Run a session against an emulated dongle until `count` scan restarts have happened.
+    #[cfg(target_os = "linux")]
+    async fn collect_restarts(scan: BleuIOCommand, silence_window: Option<Duration>, count: usize) -> Vec<RestartReason> {
+        use crate::models::bleuio_emulator::*;
+
+        const ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let emulator = BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap();
+
+        let mut session = BleuIOSession::new(DongleTarget::Port(emulator.slave_path().to_string()));
+        session.set_scan_command(scan);
+        session.set_silence_window(silence_window);
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut restarts = Vec::new();
+        let run = session.run(&mut rx, |event| {
+            if let SessionEvent::ScanRestarted(restart) = event {
+                restarts.push(restart.reason);
+                if restarts.len() == count {
+                    tx.take();
+                }
+            }
+        });
+        // A restart the dongle refuses ends the connection instead of producing an event.
+        tokio::time::timeout(Duration::from_secs(5), run).await.expect("scan was not restarted");
+        restarts
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watchdog_restarts_ended_scan() {
+        let scan = BleuIOCommand::AtFindscandata { pattern: "FF5B07".to_string(), duration: Some(1) };
+        let restarts = collect_restarts(scan, None, 2).await;
+        assert!(restarts.len() >= 2);
+        assert!(restarts.iter().all(|r| *r == RestartReason::ScanEnded));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_watchdog_restarts_silent_scan() {
+        // Nothing matches, so the dongle scans but never reports anything.
+        let scan = BleuIOCommand::AtFindscandata { pattern: "FFFFFF".to_string(), duration: None };
+        let window = Duration::from_millis(50);
+        let restarts = collect_restarts(scan, Some(window), 2).await;
+        assert_eq!(restarts, vec![RestartReason::Silence(window), RestartReason::Silence(window)]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_session_keeps_user_stopped_scan() {
+        use crate::models::bleuio_emulator::*;
+
+        const ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let emulator = BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap();
+        let mut session = BleuIOSession::new(DongleTarget::Port(emulator.slave_path().to_string()));
+        session.set_silence_window(Some(Duration::from_millis(50)));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut restarts = 0;
+        let mut stopped = false;
+        let run = session.run(&mut rx, |event| match event {
+            SessionEvent::ScanResult(BleuIOResponse::ScanEnded { .. }) => stopped = true,
+            SessionEvent::ScanResult(_) => {
+                tx.unbounded_send(SessionCommand::Send(BleuIOCommand::StopScan)).ok();
+            }
+            SessionEvent::ScanRestarted(_) => restarts += 1,
+            _ => {}
+        });
+        // Neither the SE nor the silence that follows may start the scan again.
+        tokio::time::timeout(Duration::from_millis(500), run).await.ok();
+
+        assert!(stopped);
+        assert_eq!(restarts, 0);
+        assert!(!emulator.is_scanning());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_session_stops_scan_on_shutdown() {
+        use crate::models::bleuio_emulator::*;
+
+        const ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let emulator = BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap();
+        let mut session = BleuIOSession::new(DongleTarget::Port(emulator.slave_path().to_string()));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut scanning_while_running = false;
+        session
+            .run(&mut rx, |event| {
+                if let SessionEvent::ScanResult(_) = event {
+                    scanning_while_running = emulator.is_scanning();
+                    tx.take();
+                }
+            })
+            .await;
+
+        assert!(scanning_while_running);
+        assert!(!emulator.is_scanning());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_session_reports_partial_autoexec_write() {
+        use crate::models::bleuio_emulator::*;
+
+        let emulator = BleuIOEmulator::start(Vec::new()).unwrap();
+        let mut session = BleuIOSession::new(DongleTarget::Port(emulator.slave_path().to_string()));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut failed = None;
+        let mut lists = Vec::new();
+        let run = session.run(&mut rx, |event| match event {
+            SessionEvent::Connected(_) => {
+                let commands = vec!["AT+CENTRAL".to_string(), "BOGUS".to_string(), "AT+FINDSCANDATA=FF5B07".to_string()];
+                tx.as_ref().unwrap().unbounded_send(SessionCommand::WriteAutoexec(commands)).unwrap();
+            }
+            SessionEvent::CommandFailed(reason) => failed = Some(reason),
+            SessionEvent::Autoexec(list) => {
+                lists.push(list);
+                tx.take();
+            }
+            _ => {}
+        });
+        tokio::time::timeout(Duration::from_secs(5), run).await.expect("autoexec was not read back");
+
+        assert!(failed.is_some_and(|reason| reason.starts_with("Autoexec:")));
+        assert_eq!(lists, vec![vec!["AT+CENTRAL".to_string()]]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_session_reads_sensor() {
+        use crate::models::bleuio_emulator::*;
+
+        const ADVERT: &str = "0201061BFF5B07050422005A0000BA27C60017013E0000000000000001C002";
+        let emulator = BleuIOEmulator::start(vec![EmulatedAdvert::new("F5:50:35:CF:B1:ED", -61, ADVERT)]).unwrap();
+        let mut session = BleuIOSession::new(DongleTarget::Port(emulator.slave_path().to_string()));
+
+        let (tx, mut rx) = futures_channel::mpsc::unbounded::<SessionCommand>();
+        let mut tx = Some(tx);
+        let mut events = Vec::new();
+        let mut scans_after_read = 0;
+        session
+            .run(&mut rx, |event| {
+                match &event {
+                    SessionEvent::Connected(_) => {
+                        let addr = "F5:50:35:CF:B1:ED".parse().unwrap();
+                        tx.as_ref().unwrap().unbounded_send(SessionCommand::ReadSensor(addr)).unwrap();
+                    }
+                    // Scanning resumes once the sensor has been read.
+                    SessionEvent::ScanResult(_) if events.iter().any(|e| matches!(e, SessionEvent::Gatt(GattEvent::Disconnected(..)))) => {
+                        scans_after_read += 1;
+                        tx.take();
+                    }
+                    _ => {}
+                }
+                events.push(event);
+            })
+            .await;
+
+        let gatt: Vec<GattEvent> = events
+            .into_iter()
+            .filter_map(|e| match e {
+                SessionEvent::Gatt(gatt) => Some(gatt),
+                _ => None,
+            })
+            .collect();
+        let addr = "F5:50:35:CF:B1:ED".to_string();
+        assert_eq!(gatt.first(), Some(&GattEvent::Connecting(addr.clone())));
+        assert_eq!(gatt.get(1), Some(&GattEvent::Connected(addr.clone())));
+        assert_eq!(gatt.last(), Some(&GattEvent::Disconnected(addr.clone(), "22".to_string())));
+        let values: Vec<String> = gatt
+            .iter()
+            .filter_map(|e| match e {
+                GattEvent::Value(_, value) => Some(format!("{}: {}", value.get_label(), value.get_display())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(values, vec![
+            "Device name: HibouAir",
+            "Manufacturer: Smart Sensor Devices",
+            "Model number: HibouAir CO2",
+            "Firmware revision: 1.4.2",
+            "Battery level: 87 %",
+        ]);
+        assert!(scans_after_read > 0);
+    }
+
+    #[test]
+    fn test_scan_restart_description() {
+        let at = DateTime::parse_from_rfc3339("2026-01-20T10:00:00+01:00").unwrap().with_timezone(&Local);
+        let restart = ScanRestart { reason: RestartReason::Silence(Duration::from_secs(60)), at };
+        assert!(restart.get_description().ends_with("no results for 60 s"));
+    }
+}
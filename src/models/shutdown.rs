@@ -0,0 +1,93 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+
+// Coordinates an orderly shutdown. Whoever wants the app to quit (window close, SIGTERM)
+// calls `request`; every task that has cleanup to do holds a `ShutdownGuard` while it runs,
+// and `wait_idle` returns once all guards are gone.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: Arc<watch::Sender<bool>>,
+    active: Arc<watch::Sender<usize>>,
+}
+
+// Held by a running task; dropping it tells `wait_idle` the task is done.
+pub struct ShutdownGuard {
+    active: Arc<watch::Sender<usize>>,
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        self.active.send_modify(|n| *n -= 1);
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Shutdown {
+            requested: Arc::new(watch::channel(false).0),
+            active: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    pub fn request(&self) {
+        self.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    // Complete once a shutdown has been requested.
+    pub async fn requested(&self) {
+        let mut rx = self.requested.subscribe();
+        rx.wait_for(|r| *r).await.ok();
+    }
+
+    // Register a task that must finish before the app exits.
+    pub fn guard(&self) -> ShutdownGuard {
+        self.active.send_modify(|n| *n += 1);
+        ShutdownGuard { active: self.active.clone() }
+    }
+
+    // Wait until every guard is dropped. Returns false if that took longer than `limit`.
+    pub async fn wait_idle(&self, limit: Duration) -> bool {
+        let mut rx = self.active.subscribe();
+        let idle = timeout(limit, rx.wait_for(|n| *n == 0)).await;
+        idle.is_ok()
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_guards() {
+        let shutdown = Shutdown::new();
+        assert!(shutdown.wait_idle(Duration::from_millis(10)).await);
+
+        let task = {
+            let shutdown = shutdown.clone();
+            let guard = shutdown.guard();
+            tokio::spawn(async move {
+                shutdown.requested().await;
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                drop(guard);
+            })
+        };
+
+        assert!(!shutdown.is_requested());
+        assert!(!shutdown.wait_idle(Duration::from_millis(10)).await);
+        shutdown.request();
+        assert!(shutdown.is_requested());
+        assert!(shutdown.wait_idle(Duration::from_secs(5)).await);
+        task.await.unwrap();
+    }
+}
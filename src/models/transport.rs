@@ -0,0 +1,339 @@
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::time::{timeout, Duration};
+
+// Dongles can also be reached over the network, e.g. a Raspberry Pi running ser2net:
+//   tcp://host:port       raw TCP, bytes are passed through as is
+//   rfc2217://host:port   telnet with the RFC 2217 COM port option, which also sets the baud rate
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Telnet bytes (RFC 854) and the COM port option (RFC 2217).
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+const SE: u8 = 240;
+const OPT_BINARY: u8 = 0;
+const OPT_SGA: u8 = 3;
+const OPT_COM_PORT: u8 = 44;
+const COM_SET_BAUDRATE: u8 = 1;
+const COM_SET_DATASIZE: u8 = 2;
+const COM_SET_PARITY: u8 = 3;
+const COM_SET_STOPSIZE: u8 = 4;
+const COM_SET_CONTROL: u8 = 5;
+const PARITY_NONE: u8 = 1;
+const STOPSIZE_1: u8 = 1;
+const CONTROL_DTR_ON: u8 = 8;
+const CONTROL_RTS_ON: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkProtocol {
+    Raw,        // tcp://
+    Rfc2217,    // rfc2217://
+}
+
+// Split "tcp://host:port" or "rfc2217://host:port" into protocol and "host:port".
+// Returns None for anything else, e.g. a local device path.
+pub fn parse_network_address(address: &str) -> Option<(NetworkProtocol, String)> {
+    let (protocol, rest) = if let Some(rest) = address.strip_prefix("tcp://") {
+        (NetworkProtocol::Raw, rest)
+    } else if let Some(rest) = address.strip_prefix("rfc2217://") {
+        (NetworkProtocol::Rfc2217, rest)
+    } else {
+        return None;
+    };
+    let (host, port) = rest.trim_end_matches('/').rsplit_once(':')?;
+    if host.is_empty() || port.parse::<u16>().is_err() {
+        return None;
+    }
+    Some((protocol, format!("{host}:{port}")))
+}
+
+// Open a TCP connection, giving up after a few seconds.
+pub async fn connect_tcp(addr: &str) -> std::io::Result<TcpStream> {
+    let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, format!("Connecting to {addr} timed out")))??;
+    stream.set_nodelay(true).ok();
+    Ok(stream)
+}
+
+// Double every IAC byte so data isn't taken for a telnet command.
+pub fn escape_iac(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for &byte in data {
+        out.push(byte);
+        if byte == IAC {
+            out.push(IAC);
+        }
+    }
+    out
+}
+
+// One COM port option subnegotiation: IAC SB 44 <command> <value> IAC SE.
+fn com_port_command(command: u8, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![IAC, SB, OPT_COM_PORT, command];
+    out.extend(escape_iac(value));
+    out.extend([IAC, SE]);
+    out
+}
+
+// What the client sends right after connecting: binary mode, the COM port option, and
+// the serial settings the dongle needs (baud, 8N1, DTR and RTS on).
+pub fn rfc2217_setup(baud: u32) -> Vec<u8> {
+    let mut out = vec![IAC, WILL, OPT_BINARY, IAC, DO, OPT_BINARY, IAC, WILL, OPT_COM_PORT];
+    out.extend(com_port_command(COM_SET_BAUDRATE, &baud.to_be_bytes()));
+    out.extend(com_port_command(COM_SET_DATASIZE, &[8]));
+    out.extend(com_port_command(COM_SET_PARITY, &[PARITY_NONE]));
+    out.extend(com_port_command(COM_SET_STOPSIZE, &[STOPSIZE_1]));
+    out.extend(com_port_command(COM_SET_CONTROL, &[CONTROL_DTR_ON]));
+    out.extend(com_port_command(COM_SET_CONTROL, &[CONTROL_RTS_ON]));
+    out
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TelnetState {
+    Data,
+    Iac,
+    Option(u8),     // got IAC and WILL/WONT/DO/DONT, waiting for the option
+    Sub,            // inside IAC SB ... IAC SE
+    SubIac,
+}
+
+// Separates serial data from telnet commands in what the server sends, and produces the
+// answers to the server's option requests. Subnegotiations (e.g. the server confirming the
+// baud rate) are skipped.
+pub struct TelnetDecoder {
+    state: TelnetState,
+    will_sent: Vec<u8>,     // options we offered, their DO is an acknowledgement
+    do_sent: Vec<u8>,       // options we asked for, their WILL is an acknowledgement
+}
+
+impl TelnetDecoder {
+    pub fn new() -> Self {
+        TelnetDecoder {
+            state: TelnetState::Data,
+            will_sent: vec![OPT_BINARY, OPT_COM_PORT],
+            do_sent: vec![OPT_BINARY],
+        }
+    }
+
+    // Decode `input`, appending serial data to `data` and telnet answers to `replies`.
+    pub fn feed(&mut self, input: &[u8], data: &mut Vec<u8>, replies: &mut Vec<u8>) {
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (TelnetState::Data, IAC) => TelnetState::Iac,
+                (TelnetState::Data, _) => {
+                    data.push(byte);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, IAC) => {
+                    data.push(IAC);
+                    TelnetState::Data
+                }
+                (TelnetState::Iac, WILL | WONT | DO | DONT) => TelnetState::Option(byte),
+                (TelnetState::Iac, SB) => TelnetState::Sub,
+                // NOP, GA and other two byte commands.
+                (TelnetState::Iac, _) => TelnetState::Data,
+                (TelnetState::Option(command), option) => {
+                    self.answer(command, option, replies);
+                    TelnetState::Data
+                }
+                (TelnetState::Sub, IAC) => TelnetState::SubIac,
+                (TelnetState::Sub, _) => TelnetState::Sub,
+                (TelnetState::SubIac, SE) => TelnetState::Data,
+                (TelnetState::SubIac, _) => TelnetState::Sub,
+            };
+        }
+    }
+
+    fn answer(&mut self, command: u8, option: u8, replies: &mut Vec<u8>) {
+        let supported = matches!(option, OPT_BINARY | OPT_SGA | OPT_COM_PORT);
+        match command {
+            DO if !self.will_sent.contains(&option) => {
+                replies.extend([IAC, if supported { WILL } else { WONT }, option]);
+                if supported {
+                    self.will_sent.push(option);
+                }
+            }
+            WILL if !self.do_sent.contains(&option) => {
+                replies.extend([IAC, if supported { DO } else { DONT }, option]);
+                if supported {
+                    self.do_sent.push(option);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Default for TelnetDecoder {
+    fn default() -> Self {
+        TelnetDecoder::new()
+    }
+}
+
+// Run the RFC 2217 protocol on `stream` and return a plain byte stream for
+// `BleuIOClient::from_stream`. Closing either side closes the other.
+pub fn rfc2217(stream: TcpStream, baud: u32) -> DuplexStream {
+    let (host, local) = tokio::io::duplex(64 * 1024);
+    let (mut local_rx, mut local_tx) = tokio::io::split(local);
+    let (mut net_rx, net_tx) = stream.into_split();
+    let net_tx = Arc::new(Mutex::new(net_tx));
+
+    // Client → server: setup first, then escaped data.
+    let upstream_tx = net_tx.clone();
+    tokio::spawn(async move {
+        if upstream_tx.lock().await.write_all(&rfc2217_setup(baud)).await.is_err() {
+            return;
+        }
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = local_rx.read(&mut buf).await {
+            if n == 0 || upstream_tx.lock().await.write_all(&escape_iac(&buf[..n])).await.is_err() {
+                break;
+            }
+        }
+        upstream_tx.lock().await.shutdown().await.ok();
+    });
+
+    // Server → client: data goes to the client, option answers back to the server.
+    tokio::spawn(async move {
+        let mut decoder = TelnetDecoder::new();
+        let mut buf = [0u8; 1024];
+        while let Ok(n) = net_rx.read(&mut buf).await {
+            if n == 0 {
+                break;
+            }
+            let mut data = Vec::new();
+            let mut replies = Vec::new();
+            decoder.feed(&buf[..n], &mut data, &mut replies);
+            if !replies.is_empty() && net_tx.lock().await.write_all(&replies).await.is_err() {
+                break;
+            }
+            if !data.is_empty() && local_tx.write_all(&data).await.is_err() {
+                break;
+            }
+        }
+        local_tx.shutdown().await.ok();
+    });
+
+    host
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bleuio::*;
+    use crate::models::bleuio_client::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_network_address() {
+        assert_eq!(parse_network_address("tcp://pi-room12:3333"), Some((NetworkProtocol::Raw, "pi-room12:3333".to_string())));
+        assert_eq!(parse_network_address("rfc2217://10.0.0.5:2217/"), Some((NetworkProtocol::Rfc2217, "10.0.0.5:2217".to_string())));
+        assert_eq!(parse_network_address("tcp://[::1]:3333"), Some((NetworkProtocol::Raw, "[::1]:3333".to_string())));
+        assert_eq!(parse_network_address("/dev/ttyACM0"), None);
+        assert_eq!(parse_network_address("tcp://pi-room12"), None);
+        assert_eq!(parse_network_address("tcp://:3333"), None);
+        assert_eq!(parse_network_address("udp://pi:3333"), None);
+    }
+
+    #[test]
+    fn test_rfc2217_setup() {
+        let setup = rfc2217_setup(115200);
+        let baud = [IAC, SB, OPT_COM_PORT, COM_SET_BAUDRATE, 0x00, 0x01, 0xC2, 0x00, IAC, SE];
+        assert!(setup.windows(baud.len()).any(|w| w == baud));
+        assert!(setup.starts_with(&[IAC, WILL, OPT_BINARY]));
+
+        // A value byte of 0xFF must be doubled inside the subnegotiation.
+        let odd = com_port_command(COM_SET_BAUDRATE, &[0, 0, 0xFF, 0]);
+        assert_eq!(odd, vec![IAC, SB, OPT_COM_PORT, COM_SET_BAUDRATE, 0, 0, IAC, IAC, 0, IAC, SE]);
+        assert_eq!(escape_iac(b"A\xFFB"), b"A\xFF\xFFB");
+    }
+
+    #[test]
+    fn test_telnet_decoder() {
+        let mut decoder = TelnetDecoder::new();
+        let mut data = Vec::new();
+        let mut replies = Vec::new();
+
+        // Data split across reads, an escaped 0xFF, a NOP, an ack of our own offer,
+        // a subnegotiation and two requests that need answers.
+        decoder.feed(b"{\"E\":1,", &mut data, &mut replies);
+        decoder.feed(&[IAC, 241, IAC, DO, OPT_COM_PORT, IAC, SB, OPT_COM_PORT, 101, 0, 1, 0xC2, 0, IAC, SE], &mut data, &mut replies);
+        decoder.feed(&[b'x', IAC, IAC, IAC], &mut data, &mut replies);
+        decoder.feed(&[WILL, OPT_SGA, IAC, DO, 24, b'y'], &mut data, &mut replies);
+
+        assert_eq!(data, b"{\"E\":1,x\xFFy");
+        assert_eq!(replies, vec![IAC, DO, OPT_SGA, IAC, WONT, 24]);
+
+        // Asked again: already agreed, no new answer.
+        replies.clear();
+        decoder.feed(&[IAC, WILL, OPT_SGA], &mut data, &mut replies);
+        assert!(replies.is_empty());
+    }
+
+    // Answer every command line with an empty C/A/E sequence, like a dongle in verbose mode.
+    async fn answer_commands(mut stream: TcpStream, telnet: bool) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut idx = 0;
+        let mut buf = [0u8; 1024];
+        loop {
+            let n = stream.read(&mut buf).await.unwrap();
+            if n == 0 {
+                return received;
+            }
+            received.extend_from_slice(&buf[..n]);
+            while received.ends_with(b"\r\n") && received.len() > idx {
+                idx = received.len();
+                let mut reply = Vec::new();
+                if telnet {
+                    reply.extend([IAC, WILL, OPT_SGA]);
+                }
+                reply.extend(b"{\"C\":1,\"cmd\":\"AT\"}\r\n{\"A\":1,\"err\":0}\r\n{\"E\":1,\"nol\":2}\r\n");
+                stream.write_all(&reply).await.unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_raw_tcp_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("tcp://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move { answer_commands(listener.accept().await.unwrap().0, false).await });
+
+        let source = BleuIOSource::from_port_name(&url);
+        assert!(matches!(source, BleuIOSource::Network { protocol: NetworkProtocol::Raw, .. }));
+        assert_eq!(source.to_string(), url);
+
+        let client = BleuIOClient::connect(&source, &[]).await.unwrap();
+        assert_eq!(client.detect_mode().await.unwrap(), DongleMode { echo: false, verbose: true });
+        client.close().await;
+
+        assert_eq!(server.await.unwrap(), b"\x03AT\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_rfc2217_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("rfc2217://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move { answer_commands(listener.accept().await.unwrap().0, true).await });
+
+        let client = BleuIOClient::connect(&BleuIOSource::from_port_name(&url), &[]).await.unwrap();
+        client.send(BleuIOCommand::At).await.unwrap();
+        client.send(BleuIOCommand::At).await.unwrap();
+        client.close().await;
+
+        let received = server.await.unwrap();
+        let setup = rfc2217_setup(115200);
+        assert!(received.starts_with(&setup));
+        // The server's WILL SGA was answered once, between the two commands.
+        let rest = &received[setup.len()..];
+        assert_eq!(rest, [b"AT\r\n".as_slice(), &[IAC, DO, OPT_SGA], b"AT\r\n"].concat());
+    }
+}